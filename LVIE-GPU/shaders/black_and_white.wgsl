@@ -0,0 +1,65 @@
+// implementation of LVIElib::channel_mixer::black_and_white
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var output_texture : texture_storage_2d<rgba8unorm, write>;
+// the enabled flag and the luminance of the 8 hue bands
+@group(0) @binding(2) var <storage, read> parameters : array<f32>;
+
+// OkLch hue of the center of every band, the same of LVIElib::hsl_mixer
+const BAND_CENTERS: array<f32, 8> = array<f32, 8>(29.2, 53.4, 109.8, 142.5, 194.8, 264.1, 294.3, 328.4);
+
+fn linsrgb_to_oklab(rgb: vec3<f32>) -> vec3<f32> {
+    let lms = vec3<f32>(
+        dot(vec3<f32>(0.4122214708, 0.5363325363, 0.0514459929), rgb),
+        dot(vec3<f32>(0.2119034982, 0.6806995451, 0.1073969566), rgb),
+        dot(vec3<f32>(0.0883024619, 0.2817188376, 0.6299787005), rgb),
+    );
+    let lms_ = sign(lms) * pow(abs(lms), vec3<f32>(1.0 / 3.0));
+    return vec3<f32>(
+        dot(vec3<f32>(0.2104542553, 0.7936177850, -0.0040720468), lms_),
+        dot(vec3<f32>(1.9779984951, -2.4285922050, 0.4505937099), lms_),
+        dot(vec3<f32>(0.0259040371, 0.7827717662, -0.8086757660), lms_),
+    );
+}
+
+// the weights of the hue bands blended like LVIElib::hsl_mixer::band_weights
+fn hue_exposure(hue: f32) -> f32 {
+    // constant arrays can only be indexed by constants
+    var centers = BAND_CENTERS;
+    var i = 7;
+    for (var band = 0; band < 8; band++) {
+        if (centers[band] <= hue) {
+            i = band;
+        }
+    }
+    let next = (i + 1) % 8;
+
+    let width = (centers[next] - centers[i] + 360.0) % 360.0;
+    let t = (hue - centers[i] + 360.0) % 360.0 / width;
+    let s = t * t * (3.0 - 2.0 * t);
+    return (1.0 - s) * parameters[1 + i] + s * parameters[1 + next];
+}
+
+@compute @workgroup_size(16, 16)
+fn shader_main(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(input_texture);
+    let coords = vec2<u32>(global_id.xy);
+
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords.xy, 0);
+    let lab = linsrgb_to_oklab(pow(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2)));
+    let chroma = length(lab.yz);
+    let hue = (degrees(atan2(lab.z, lab.y)) + 360.0) % 360.0;
+
+    // grays have no hue, so they keep their lightness
+    let colorfulness = min(chroma / 0.1, 1.0);
+    let l = min(lab.x * exp2(hue_exposure(hue) * colorfulness), 1.0);
+
+    // the lightness of a gray in OkLab is the cube root of its linear light
+    let gray = pow(l * l * l, 1.0 / 2.2);
+    textureStore(output_texture, coords.xy, vec4<f32>(gray, gray, gray, color.a));
+}
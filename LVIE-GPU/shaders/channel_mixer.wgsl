@@ -0,0 +1,38 @@
+// implementation of LVIElib::channel_mixer::mix_channels
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var output_texture : texture_storage_2d<rgba8unorm, write>;
+// the rows of the matrix, the offsets of red, green and blue and the preserve luminosity flag
+@group(0) @binding(2) var <storage, read> parameters : array<f32>;
+
+const LUMINANCE: vec3<f32> = vec3<f32>(0.2126, 0.7152, 0.0722);
+
+@compute @workgroup_size(16, 16)
+fn shader_main(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(input_texture);
+    let coords = vec2<u32>(global_id.xy);
+
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+
+    let color = textureLoad(input_texture, coords.xy, 0);
+    // the channels are mixed in linear light
+    let lin = pow(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2));
+    var mixed = vec3<f32>(
+        dot(vec3<f32>(parameters[0], parameters[1], parameters[2]), lin) + parameters[9],
+        dot(vec3<f32>(parameters[3], parameters[4], parameters[5]), lin) + parameters[10],
+        dot(vec3<f32>(parameters[6], parameters[7], parameters[8]), lin) + parameters[11],
+    );
+
+    if (parameters[12] != 0.0) {
+        let after = dot(LUMINANCE, mixed);
+        if (after > 0.000001) {
+            mixed = mixed * dot(LUMINANCE, lin) / after;
+        }
+    }
+
+    let out = pow(clamp(mixed, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(1.0 / 2.2));
+    textureStore(output_texture, coords.xy, vec4<f32>(out, color.a));
+}
@@ -0,0 +1,44 @@
+// implementation of the vignette of LVIE::core::processors
+@group(0) @binding(0) var input_texture : texture_2d<f32>;
+@group(0) @binding(1) var output_texture : texture_storage_2d<rgba8unorm, write>;
+// amount, midpoint, roundness, feather and the x, y, width and height of the frame in pixels
+@group(0) @binding(2) var <storage, read> incremental : array<f32>;
+
+fn vignette_weight(u: f32, v: f32, midpoint: f32, roundness: f32, feather: f32) -> f32 {
+    let exponent = 2.0 + 6.0 * max(-roundness, 0.0);
+    let d = pow(pow(abs(u), exponent) + pow(abs(v), exponent), 1.0 / exponent);
+    let t = clamp((d - midpoint) / (feather + 0.001), 0.0, 1.0);
+    return t * t * (3.0 - 2.0 * t);
+}
+
+@compute @workgroup_size(16, 16)
+fn shader_main(
+  @builtin(global_invocation_id) global_id : vec3<u32>,
+) {
+    let dimensions = textureDimensions(input_texture);
+    let coords = vec2<u32>(global_id.xy);
+
+    if(coords.x >= dimensions.x || coords.y >= dimensions.y) {
+        return;
+    }
+
+    let amount = incremental[0];
+    let half_size = vec2<f32>(incremental[6], incremental[7]) / 2.0;
+    let center = vec2<f32>(incremental[4], incremental[5]) + half_size;
+    // a round vignette has the same radius on both the axes
+    let radius = sqrt(half_size.x * half_size.y);
+    let scale = mix(half_size, vec2<f32>(radius), max(incremental[2], 0.0));
+    let uv = (vec2<f32>(coords.xy) + 0.5 - center) / scale;
+    let t = vignette_weight(uv.x, uv.y, incremental[1], incremental[2], incremental[3]);
+
+    let color = textureLoad(input_texture, coords.xy, 0);
+    // the vignette works in linear light
+    let lin = pow(clamp(color.rgb, vec3<f32>(0.0), vec3<f32>(1.0)), vec3<f32>(2.2));
+    var shaded = lin;
+    if (amount < 0.0) {
+        shaded = lin * (1.0 + amount * t);
+    } else {
+        shaded = lin + (1.0 - lin) * amount * t;
+    }
+    textureStore(output_texture, coords.xy, vec4<f32>(pow(shaded, vec3<f32>(1.0 / 2.2)), color.a));
+}
@@ -48,6 +48,9 @@ pub enum GPUShaderType {
     Saturation,
     Grayscale,
     WhiteBalance,
+    Vignette,
+    ChannelMixer,
+    BlackAndWhite,
 }
 
 impl GPUShaderType {
@@ -265,7 +268,40 @@ impl GPU {
                 ),
             });
 
-        self.shaders = vec![exposition, saturation, grayscale, whitebalance];
+        let vignette = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Vignette shader"),
+                source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/vignette.wgsl").into()),
+            });
+
+        let channel_mixer = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Channel Mixer shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/channel_mixer.wgsl").into(),
+                ),
+            });
+
+        let black_and_white = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: Some("Black and White shader"),
+                source: wgpu::ShaderSource::Wgsl(
+                    include_str!("../shaders/black_and_white.wgsl").into(),
+                ),
+            });
+
+        self.shaders = vec![
+            exposition,
+            saturation,
+            grayscale,
+            whitebalance,
+            vignette,
+            channel_mixer,
+            black_and_white,
+        ];
     }
 
     #[allow(unreachable_code)]
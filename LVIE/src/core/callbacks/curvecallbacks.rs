@@ -6,8 +6,9 @@ use crate::history::{CurveOperationType, History};
 
 use super::super::{
     super::ui::{CurveCallbacks, LVIE},
-    Data,
+    CurveChannel, CurveType, Data,
 };
+use super::update_preview;
 
 pub fn init_curve_callbacks<P>(
     Window: Weak<LVIE>,
@@ -38,7 +39,7 @@ pub fn init_curve_callbacks<P>(
         move |points: slint::ModelRc<slint::ModelRc<f32>>| {
             let mut data = dw.lock().unwrap();
 
-            let (mut xs, mut ys) = data.curve.get_raw_data();
+            let (mut xs, mut ys) = data.selected_curve().get_raw_data();
 
             for (i, point) in points.iter().enumerate() {
                 let p: Vec<f32> = point.iter().collect();
@@ -48,12 +49,14 @@ pub fn init_curve_callbacks<P>(
                 }
             }
 
-            data.curve.update_curve(xs, ys);
+            data.selected_curve_mut().update_curve(xs, ys);
 
             let W = ww.unwrap();
 
-            W.set_curve(data.curve.to_image((300, 300)));
-            W.set_curve_points(data.curve.into_rc_model());
+            W.set_curve(data.selected_curve().to_image((300, 300)));
+            W.set_curve_points(data.selected_curve().into_rc_model());
+
+            update_preview(&W, &mut data);
         },
     );
 
@@ -64,15 +67,23 @@ pub fn init_curve_callbacks<P>(
         .global::<CurveCallbacks>()
         .on_add_curve_point(move |x: f32, y: f32| {
             let mut d = dw.lock().unwrap();
-            let i = d.curve.add_point([x, y]).expect("Failed to add a point");
-
-            hw.lock().unwrap().register_Curve_Operation_without_saving(
-                &CurveOperationType::CurvePointAdded(i, x, y),
-            );
+            let i = d
+                .selected_curve_mut()
+                .add_point([x, y])
+                .expect("Failed to add a point");
+
+            hw.lock()
+                .unwrap()
+                .register_Curve_Operation_without_saving(&(
+                    d.curve_channel,
+                    CurveOperationType::CurvePointAdded(i, x, y),
+                ));
 
             let Window = ww.unwrap();
-            Window.set_curve(d.curve.to_image((300, 300)));
-            Window.set_curve_points(d.curve.into_rc_model());
+            Window.set_curve(d.selected_curve().to_image((300, 300)));
+            Window.set_curve_points(d.selected_curve().into_rc_model());
+
+            update_preview(&Window, &mut d);
 
             return i.try_into().unwrap();
         });
@@ -84,7 +95,7 @@ pub fn init_curve_callbacks<P>(
 
             let data = d_w.try_lock().unwrap();
 
-            let cps = data.curve.get_points();
+            let cps = data.selected_curve().get_points();
 
             for (i, coords) in cps.iter().enumerate() {
                 let xr = width * coords[0] / 100.0 - size / 2.0;
@@ -107,15 +118,20 @@ pub fn init_curve_callbacks<P>(
         .global::<CurveCallbacks>()
         .on_remove_curve_point(move |index: i32| {
             let mut d = d_w.lock().unwrap();
-            match d.curve.remove_point(index as usize) {
+            match d.selected_curve_mut().remove_point(index as usize) {
                 Ok((x, y)) => {
-                    hw.lock().unwrap().register_Curve_Operation_without_saving(
-                        &CurveOperationType::CurvePointRemoved(index as usize, x, y),
-                    );
+                    hw.lock()
+                        .unwrap()
+                        .register_Curve_Operation_without_saving(&(
+                            d.curve_channel,
+                            CurveOperationType::CurvePointRemoved(index as usize, x, y),
+                        ));
 
                     let Window = ww.unwrap();
-                    Window.set_curve(d.curve.to_image((300, 300)));
-                    Window.set_curve_points(d.curve.into_rc_model());
+                    Window.set_curve(d.selected_curve().to_image((300, 300)));
+                    Window.set_curve_points(d.selected_curve().into_rc_model());
+
+                    update_preview(&Window, &mut d);
                 }
                 Err(err) => {
                     println!("{:?}", err);
@@ -132,30 +148,58 @@ pub fn init_curve_callbacks<P>(
             let mut d = d_w.lock().unwrap();
 
             let new_c_type = match curve_type {
-                0 => crate::core::CurveType::MONOTONE,
-                1 => crate::core::CurveType::SMOOTH,
+                0 => CurveType::MONOTONE,
+                1 => CurveType::SMOOTH,
                 _ => unimplemented!(),
             };
 
-            if d.curve.get_curve_type() != &new_c_type {
-                hw.lock().unwrap().register_Curve_Operation_without_saving(
-                    &CurveOperationType::CurveTypeChanged(d.curve.get_curve_type().clone()),
-                );
-                d.curve.set_curve_type(new_c_type);
-                ww.unwrap().set_curve(d.curve.to_image((300, 300)));
+            let old_c_type = *d.selected_curve().get_curve_type();
+            if old_c_type != new_c_type {
+                hw.lock()
+                    .unwrap()
+                    .register_Curve_Operation_without_saving(&(
+                        d.curve_channel,
+                        CurveOperationType::CurveTypeChanged(old_c_type, new_c_type),
+                    ));
+                d.selected_curve_mut().set_curve_type(new_c_type);
+
+                let Window = ww.unwrap();
+                Window.set_curve(d.selected_curve().to_image((300, 300)));
+
+                update_preview(&Window, &mut d);
             }
         });
 
+    let d_w = DATA.clone();
+    let ww = Window.as_weak();
+    Window
+        .global::<CurveCallbacks>()
+        .on_set_curve_channel(move |channel: i32| {
+            let mut d = d_w.lock().unwrap();
+            d.curve_channel =
+                CurveChannel::from_index(channel as usize).expect("Unknown curve channel");
+
+            let Window = ww.unwrap();
+            Window.set_curve(d.selected_curve().to_image((300, 300)));
+            Window.set_curve_points(d.selected_curve().into_rc_model());
+            Window
+                .global::<CurveCallbacks>()
+                .set_current_curve_type(*d.selected_curve().get_curve_type() as i32);
+        });
+
     let dw = DATA.clone();
     let hw = HISTORY.clone();
     Window
         .global::<CurveCallbacks>()
         .on_update_history(move |index, x, y| {
             let data = dw.lock().unwrap();
-            let p = data.curve.get_point(index as usize);
-
-            hw.lock().unwrap().register_Curve_Operation_without_saving(
-                &CurveOperationType::CurvePointMoved(index as usize, x, y, p[0], p[1]),
-            )
+            let p = data.selected_curve().get_point(index as usize);
+
+            hw.lock()
+                .unwrap()
+                .register_Curve_Operation_without_saving(&(
+                    data.curve_channel,
+                    CurveOperationType::CurvePointMoved(index as usize, x, y, p[0], p[1]),
+                ))
         });
 }
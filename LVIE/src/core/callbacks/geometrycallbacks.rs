@@ -0,0 +1,560 @@
+use std::sync::{Arc, Mutex};
+
+use slint::{ComponentHandle, Weak};
+
+use crate::history::{GeometricOperationType, History};
+use crate::settings::lens_profiles::{load_lens_profiles, save_lens_profiles, LensProfile};
+
+use super::super::{
+    super::ui::{GeometryCallbacks, LVIE},
+    AspectRatio, Crop, Data, Interpolation, LensModel, Perspective, Rotation,
+};
+use super::update_preview;
+
+pub fn init_geometry_callbacks<P>(
+    Window: Weak<LVIE>,
+    DATA: Arc<Mutex<Data<P>>>,
+    HISTORY: Arc<Mutex<History>>,
+) where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    let Window = Window.unwrap();
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_set_preview(move |full: bool, unwarped: bool| {
+            let mut data = dw.lock().unwrap();
+            // the preview changes only if the stages turned on or off do something
+            let changed = (data.uncropped_preview != full && !data.crop.is_full())
+                || (data.unwarped_preview != unwarped && !data.perspective.is_identity());
+            data.uncropped_preview = full;
+            data.unwarped_preview = unwarped;
+
+            let Window = ww.unwrap();
+            Window.global::<GeometryCallbacks>().set_full_image(full);
+            if changed {
+                update_preview(&Window, &mut data);
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_move_crop_corner(move |corner: i32, x: f32, y: f32| {
+            let mut data = dw.lock().unwrap();
+            let Some(ratio) = image_ratio(&data) else {
+                return;
+            };
+            data.crop = data
+                .crop
+                .move_corner(corner as usize, [x / 100.0, y / 100.0], ratio);
+            show_crop(&ww.unwrap(), &data.crop);
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_move_crop(move |x: f32, y: f32| {
+            let mut data = dw.lock().unwrap();
+            data.crop = data.crop.moved_to(x / 100.0, y / 100.0);
+            show_crop(&ww.unwrap(), &data.crop);
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<GeometryCallbacks>().on_update_crop_history(
+        move |x: f32, y: f32, width: f32, height: f32| {
+            let data = dw.lock().unwrap();
+            let old = Crop {
+                x: x / 100.0,
+                y: y / 100.0,
+                width: width / 100.0,
+                height: height / 100.0,
+                aspect: data.crop.aspect,
+            };
+            if old != data.crop {
+                hw.lock()
+                    .unwrap()
+                    .register_Geometric_Operation_without_saving(&GeometricOperationType::Crop(
+                        old, data.crop,
+                    ));
+            }
+        },
+    );
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<GeometryCallbacks>().on_set_aspect(
+        move |index: i32, width: f32, height: f32| {
+            let mut data = dw.lock().unwrap();
+            let Some(ratio) = image_ratio(&data) else {
+                return;
+            };
+            let aspect = AspectRatio::from_index(index as usize, (width, height));
+            let new = Crop {
+                aspect,
+                ..data.crop
+            }
+            .constrained(ratio);
+            change_crop(&ww.unwrap(), &mut data, &hw, new);
+        },
+    );
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<GeometryCallbacks>().on_reset_crop(move || {
+        let mut data = dw.lock().unwrap();
+        change_crop(&ww.unwrap(), &mut data, &hw, Crop::default());
+    });
+
+    // the angle is rendered while the slider moves and stored in the history when it is released
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_change_angle(move |angle: f32| {
+            let mut data = dw.lock().unwrap();
+            data.rotation = data.rotation.with_angle(angle);
+            update_preview(&ww.unwrap(), &mut data);
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_update_angle_history(move |old: f32| {
+            let data = dw.lock().unwrap();
+            let old = data.rotation.with_angle(old);
+            if old != data.rotation {
+                hw.lock()
+                    .unwrap()
+                    .register_Geometric_Operation_without_saving(
+                        &GeometricOperationType::FreeRotation(old, data.rotation),
+                    );
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_set_interpolation(move |index: i32| {
+            let mut data = dw.lock().unwrap();
+            let interpolation = match index {
+                1 => Interpolation::Bicubic,
+                _ => Interpolation::Bilinear,
+            };
+            let new = Rotation {
+                interpolation,
+                ..data.rotation
+            };
+            change_rotation(&ww.unwrap(), &mut data, &hw, new);
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_set_auto_crop(move |auto_crop: bool| {
+            let mut data = dw.lock().unwrap();
+            let new = Rotation {
+                auto_crop,
+                ..data.rotation
+            };
+            change_rotation(&ww.unwrap(), &mut data, &hw, new);
+        });
+
+    // the horizon line is in percent of the rotated image, with the origin in the top left corner
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<GeometryCallbacks>().on_straighten(
+        move |x0: f32, y0: f32, x1: f32, y1: f32| {
+            let mut data = dw.lock().unwrap();
+            let (width, height) = data.rotated_dimensions();
+            let (width, height) = (width as f32 / 100.0, height as f32 / 100.0);
+            let new = data
+                .rotation
+                .straightened([x0 * width, y0 * height], [x1 * width, y1 * height]);
+            change_rotation(&ww.unwrap(), &mut data, &hw, new);
+        },
+    );
+
+    // the corners and the keystone sliders store the perspective when the edit begins
+    // and register a single operation when it ends
+    let edit_start = Arc::new(Mutex::new(Perspective::default()));
+
+    let dw = DATA.clone();
+    let ew = edit_start.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_begin_perspective_edit(move || {
+            *ew.lock().unwrap() = dw.lock().unwrap().perspective;
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_move_perspective_corner(move |corner: i32, x: f32, y: f32| {
+            let mut data = dw.lock().unwrap();
+            data.perspective = data
+                .perspective
+                .with_corner(corner as usize, [x / 100.0, y / 100.0]);
+            show_perspective(&ww.unwrap(), &data.perspective);
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window.global::<GeometryCallbacks>().on_change_keystone(
+        move |vertical: f32, horizontal: f32| {
+            let mut data = dw.lock().unwrap();
+            data.perspective = data.perspective.with_keystone(vertical, horizontal);
+            show_perspective(&ww.unwrap(), &data.perspective);
+            if !data.unwarped_preview {
+                update_preview(&ww.unwrap(), &mut data);
+            }
+        },
+    );
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    let ew = edit_start.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_end_perspective_edit(move || {
+            let data = dw.lock().unwrap();
+            let old = *ew.lock().unwrap();
+            if old != data.perspective {
+                hw.lock()
+                    .unwrap()
+                    .register_Geometric_Operation_without_saving(
+                        &GeometricOperationType::Perspective(old, data.perspective),
+                    );
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_reset_perspective(move || {
+            let mut data = dw.lock().unwrap();
+            let old = data.perspective;
+            data.perspective = Perspective::default();
+            show_perspective(&ww.unwrap(), &data.perspective);
+            if old != data.perspective {
+                hw.lock()
+                    .unwrap()
+                    .register_Geometric_Operation_without_saving(
+                        &GeometricOperationType::Perspective(old, data.perspective),
+                    );
+                update_preview(&ww.unwrap(), &mut data);
+            }
+        });
+
+    let profiles = Arc::new(Mutex::new(load_lens_profiles()));
+    show_lens_profiles(&Window, &profiles.lock().unwrap().names());
+
+    // the sliders of the lens store the model when they are pressed, like the perspective
+    let lens_start = Arc::new(Mutex::new(LensModel::default()));
+
+    let dw = DATA.clone();
+    let lw = lens_start.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_begin_lens_edit(move || {
+            *lw.lock().unwrap() = dw.lock().unwrap().lens;
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_change_lens(move || {
+            let mut data = dw.lock().unwrap();
+            let Window = ww.unwrap();
+            data.lens = lens_from_sliders(&Window, &data.lens);
+            update_preview(&Window, &mut data);
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    let lw = lens_start.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_end_lens_edit(move || {
+            let data = dw.lock().unwrap();
+            let old = *lw.lock().unwrap();
+            if old != data.lens {
+                hw.lock()
+                    .unwrap()
+                    .register_Geometric_Operation_without_saving(&GeometricOperationType::Lens(
+                        old, data.lens,
+                    ));
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    let pw = profiles.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_load_lens_profile(move |index: i32| {
+            let Some(lens) = pw.lock().unwrap().get(index as usize).map(|p| p.model()) else {
+                return;
+            };
+            let mut data = dw.lock().unwrap();
+            change_lens(&ww.unwrap(), &mut data, &hw, lens);
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let pw = profiles.clone();
+    Window
+        .global::<GeometryCallbacks>()
+        .on_save_lens_profile(move |name: slint::SharedString| {
+            let name = name.trim();
+            if name.is_empty() {
+                return;
+            }
+            let mut profiles = pw.lock().unwrap();
+            profiles.insert(LensProfile::new(name.into(), &dw.lock().unwrap().lens));
+            if let Err(e) = save_lens_profiles(&profiles) {
+                eprintln!("cannot save the lens profiles: {}", e);
+            }
+            show_lens_profiles(&ww.unwrap(), &profiles.names());
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<GeometryCallbacks>().on_reset_lens(move || {
+        let mut data = dw.lock().unwrap();
+        change_lens(&ww.unwrap(), &mut data, &hw, LensModel::default());
+    });
+}
+
+// the model with the values of the sliders, the coefficients without a slider are kept
+fn lens_from_sliders(Window: &LVIE, lens: &LensModel) -> LensModel {
+    let callbacks = Window.global::<GeometryCallbacks>();
+    LensModel {
+        distortion: [
+            callbacks.get_lens_distortion(),
+            callbacks.get_lens_distortion_k2(),
+            lens.distortion[2],
+        ],
+        vignetting: [
+            callbacks.get_lens_vignetting(),
+            callbacks.get_lens_vignetting_k2(),
+            lens.vignetting[2],
+        ],
+        chromatic_aberration: [
+            1.0 + callbacks.get_lens_ca_red() / 100.0,
+            1.0 + callbacks.get_lens_ca_blue() / 100.0,
+        ],
+    }
+}
+
+// the chromatic aberration is shown in percent
+pub fn show_lens(Window: &LVIE, lens: &LensModel) {
+    let callbacks = Window.global::<GeometryCallbacks>();
+    callbacks.set_lens_distortion(lens.distortion[0]);
+    callbacks.set_lens_distortion_k2(lens.distortion[1]);
+    callbacks.set_lens_vignetting(lens.vignetting[0]);
+    callbacks.set_lens_vignetting_k2(lens.vignetting[1]);
+    callbacks.set_lens_ca_red((lens.chromatic_aberration[0] - 1.0) * 100.0);
+    callbacks.set_lens_ca_blue((lens.chromatic_aberration[1] - 1.0) * 100.0);
+}
+
+fn show_lens_profiles(Window: &LVIE, names: &[String]) {
+    let names: Vec<slint::SharedString> = names.iter().map(|n| n.into()).collect();
+    Window
+        .global::<GeometryCallbacks>()
+        .set_lens_profiles(slint::ModelRc::new(slint::VecModel::from(names)));
+}
+
+// replaces the lens model, stores the change in the history and renders the image again
+fn change_lens<P>(Window: &LVIE, data: &mut Data<P>, history: &Mutex<History>, new: LensModel)
+where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    let old = data.lens;
+    data.lens = new;
+    show_lens(Window, &data.lens);
+    if old == new {
+        return;
+    }
+
+    history
+        .lock()
+        .unwrap()
+        .register_Geometric_Operation_without_saving(&GeometricOperationType::Lens(old, new));
+    update_preview(Window, data);
+}
+
+// width / height of the loaded image, `None` if there is no image
+fn image_ratio<P>(data: &Data<P>) -> Option<f32>
+where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    match data.rotated_dimensions() {
+        (_, 0) | (0, _) => None,
+        (width, height) => Some(width as f32 / height as f32),
+    }
+}
+
+// replaces the crop, stores the change in the history and renders the image again
+fn change_crop<P>(Window: &LVIE, data: &mut Data<P>, history: &Mutex<History>, new: Crop)
+where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    let old = data.crop;
+    data.crop = new;
+    show_crop(Window, &data.crop);
+    if old == new {
+        return;
+    }
+
+    history
+        .lock()
+        .unwrap()
+        .register_Geometric_Operation_without_saving(&GeometricOperationType::Crop(old, new));
+    if !data.uncropped_preview {
+        update_preview(Window, data);
+    }
+}
+
+// replaces the rotation, stores the change in the history and renders the image again
+fn change_rotation<P>(Window: &LVIE, data: &mut Data<P>, history: &Mutex<History>, new: Rotation)
+where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    let old = data.rotation;
+    data.rotation = new;
+    show_rotation(Window, &data.rotation);
+    if old == new {
+        return;
+    }
+
+    history
+        .lock()
+        .unwrap()
+        .register_Geometric_Operation_without_saving(&GeometricOperationType::FreeRotation(
+            old, new,
+        ));
+    update_preview(Window, data);
+}
+
+pub fn show_rotation(Window: &LVIE, rotation: &Rotation) {
+    let callbacks = Window.global::<GeometryCallbacks>();
+    callbacks.set_angle(rotation.angle);
+    callbacks.set_interpolation_index(match rotation.interpolation {
+        Interpolation::Bilinear => 0,
+        Interpolation::Bicubic => 1,
+    });
+    callbacks.set_auto_crop(rotation.auto_crop);
+}
+
+// shows the corners of the perspective, in percent of the image, and the keystone sliders in the UI
+pub fn show_perspective(Window: &LVIE, perspective: &Perspective) {
+    let callbacks = Window.global::<GeometryCallbacks>();
+    let corners: Vec<f32> = perspective
+        .quad()
+        .iter()
+        .flat_map(|p| [p[0] * 100.0, p[1] * 100.0])
+        .collect();
+    callbacks.set_perspective_corners(slint::ModelRc::new(slint::VecModel::from(corners)));
+    callbacks.set_vertical(perspective.vertical);
+    callbacks.set_horizontal(perspective.horizontal);
+}
+
+// shows the crop rectangle and its aspect ratio in the UI
+pub fn show_crop(Window: &LVIE, crop: &Crop) {
+    let callbacks = Window.global::<GeometryCallbacks>();
+    callbacks.set_crop_x(crop.x * 100.0);
+    callbacks.set_crop_y(crop.y * 100.0);
+    callbacks.set_crop_width(crop.width * 100.0);
+    callbacks.set_crop_height(crop.height * 100.0);
+    callbacks.set_aspect_index(crop.aspect.index() as i32);
+}
@@ -2,12 +2,16 @@ use std::sync::{Arc, Mutex};
 
 use slint::{ComponentHandle, Weak};
 
+use crate::core::{
+    BrushStroke, FilterArray, GradientMask, Mask, MaskKind, MaskTree, MaskTreeOperation, RangeMask,
+};
 use crate::history::{History, MaskOperationType};
 
 use super::super::{
     super::ui::{MaskCallbacks, LVIE},
     Data,
 };
+use super::update_preview;
 
 #[allow(unused_variables, unused_mut)]
 pub fn init_mask_callbacks<P>(
@@ -34,36 +38,34 @@ pub fn init_mask_callbacks<P>(
 {
     let Window = Window.unwrap();
 
-    let d_w = DATA.clone();
-    Window.global::<MaskCallbacks>().on_apply_mask(move || {
-        let d = d_w.lock().unwrap();
-        d.masks[0]
-            .apply_to_image(&d.full_res_preview)
-            .expect("Mask not closed");
-    });
-
     let dw = DATA.clone();
     let ww = Window.as_weak();
     let hw = HISTORY.clone();
     Window.global::<MaskCallbacks>().on_add_mask_point(
         move |x: f32, y: f32, width: f32, height: f32| {
             let mut d = dw.lock().unwrap();
-            let i = d.masks[0].add_point([x, y]);
+            // only paths can have new points
+            if !d.current_mask().is_path() {
+                return -1;
+            }
+            let i = d.current_mask_mut().add_point([x, y]);
 
             hw.lock().unwrap().register_Mask_Operation_without_saving(
-                &((0, MaskOperationType::MainPointAdded(i, x, y))),
+                &((d.selected_mask, MaskOperationType::MainPointAdded(i, x, y))),
             );
 
             let Window = ww.unwrap();
-            Window.set_mask_points(d.masks[0].into_rc_model());
-            Window.set_bezier_control_points(d.masks[0].get_control_points_model_rc());
+            Window.set_mask_points(d.current_mask().into_rc_model());
+            Window.set_bezier_control_points(d.current_mask().get_control_points_model_rc());
             #[cfg(not(debug_assertions))]
             {
                 Window.set_connection_line_points(
-                    d.masks[0].generate_line_for_slint(Some(width), Some(height)),
+                    d.current_mask_mut()
+                        .generate_line_for_slint(Some(width), Some(height)),
                 );
                 Window.set_control_point_connection_line(
-                    d.masks[0].generate_control_point_connection_lines_for_slint(),
+                    d.current_mask()
+                        .generate_control_point_connection_lines_for_slint(),
                 );
             }
             return i.try_into().unwrap();
@@ -77,7 +79,7 @@ pub fn init_mask_callbacks<P>(
 
             let data = d_w.try_lock().unwrap();
 
-            let cps = data.masks[0].get_points();
+            let cps = data.current_mask().get_handles();
 
             for (i, coords) in cps.iter().enumerate() {
                 let xr = width * coords[0] / 100.0 - size / 2.0;
@@ -101,7 +103,7 @@ pub fn init_mask_callbacks<P>(
 
             let data = d_w.try_lock().unwrap();
 
-            let cps = data.masks[0].get_control_points();
+            let cps = data.current_mask().get_control_points();
 
             for (k, coords) in cps.iter().enumerate() {
                 for (i, coord) in coords.iter().enumerate() {
@@ -125,20 +127,22 @@ pub fn init_mask_callbacks<P>(
         .on_update_mask(move |width: f32, height: f32| {
             let mut data = dw.lock().unwrap();
 
-            if data.masks[0].is_empty() {
+            if data.current_mask().is_empty() {
                 return;
             }
 
             let W = ww.unwrap();
-            W.set_mask_points(data.masks[0].into_rc_model());
-            W.set_bezier_control_points(data.masks[0].get_control_points_model_rc());
+            W.set_mask_points(data.current_mask().into_rc_model());
+            W.set_bezier_control_points(data.current_mask().get_control_points_model_rc());
             #[cfg(not(debug_assertions))]
             {
                 W.set_connection_line_points(
-                    data.masks[0].generate_line_for_slint(Some(width), Some(height)),
+                    data.current_mask_mut()
+                        .generate_line_for_slint(Some(width), Some(height)),
                 );
                 W.set_control_point_connection_line(
-                    data.masks[0].generate_control_point_connection_lines_for_slint(),
+                    data.current_mask()
+                        .generate_control_point_connection_lines_for_slint(),
                 );
             }
         });
@@ -149,20 +153,26 @@ pub fn init_mask_callbacks<P>(
     Window.global::<MaskCallbacks>().on_update_mask_point(
         move |index: i32, x: f32, y: f32, width: f32, height: f32| {
             let mut data = dw.lock().unwrap();
-            match data.masks[0].update_point(index as usize, [x, y]) {
+            match data.current_mask_mut().update_point(index as usize, [x, y]) {
                 Ok((x, y)) => {
                     let W = ww.unwrap();
-                    W.set_mask_points(data.masks[0].into_rc_model());
-                    W.set_bezier_control_points(data.masks[0].get_control_points_model_rc());
+                    W.set_mask_points(data.current_mask().into_rc_model());
+                    W.set_bezier_control_points(data.current_mask().get_control_points_model_rc());
                     #[cfg(not(debug_assertions))]
                     {
                         W.set_connection_line_points(
-                            data.masks[0].generate_line_for_slint(Some(width), Some(height)),
+                            data.current_mask_mut()
+                                .generate_line_for_slint(Some(width), Some(height)),
                         );
                         W.set_control_point_connection_line(
-                            data.masks[0].generate_control_point_connection_lines_for_slint(),
+                            data.current_mask()
+                                .generate_control_point_connection_lines_for_slint(),
                         );
                     }
+
+                    if data.current_mask_is_rendered() {
+                        update_preview(&W, &mut data);
+                    }
                 }
                 Err(err) => {
                     println!("{:?}", err);
@@ -177,12 +187,13 @@ pub fn init_mask_callbacks<P>(
     Window.global::<MaskCallbacks>().on_update_control_point(
         move |index: i32, x: f32, y: f32, width: f32, height: f32| {
             let mut data = dw.lock().unwrap();
-            match data.masks[0]
+            match data
+                .current_mask_mut()
                 .update_control_point([index as usize / 10, index as usize % 10], [x, y])
             {
                 Ok((ox, oy)) => {
                     hw.lock().unwrap().register_Mask_Operation_without_saving(&(
-                        0,
+                        data.selected_mask,
                         MaskOperationType::ControlPointMoved(
                             index as usize / 10,
                             index as usize % 10,
@@ -194,16 +205,22 @@ pub fn init_mask_callbacks<P>(
                     ));
 
                     let W = ww.unwrap();
-                    W.set_bezier_control_points(data.masks[0].get_control_points_model_rc());
+                    W.set_bezier_control_points(data.current_mask().get_control_points_model_rc());
                     #[cfg(not(debug_assertions))]
                     {
                         W.set_connection_line_points(
-                            data.masks[0].generate_line_for_slint(Some(width), Some(height)),
+                            data.current_mask_mut()
+                                .generate_line_for_slint(Some(width), Some(height)),
                         );
                         W.set_control_point_connection_line(
-                            data.masks[0].generate_control_point_connection_lines_for_slint(),
+                            data.current_mask()
+                                .generate_control_point_connection_lines_for_slint(),
                         );
                     }
+
+                    if data.current_mask_is_rendered() {
+                        update_preview(&W, &mut data);
+                    }
                 }
                 Err(err) => {
                     println!("{:?}", err);
@@ -218,25 +235,32 @@ pub fn init_mask_callbacks<P>(
     Window.global::<MaskCallbacks>().on_remove_mask_point(
         move |index: i32, width: f32, height: f32| {
             let mut d = d_w.lock().unwrap();
-            match d.masks[0].remove_point(index as usize) {
+            match d.current_mask_mut().remove_point(index as usize) {
                 Ok((x, y)) => {
                     hw.lock().unwrap().register_Mask_Operation_without_saving(&(
-                        0,
+                        d.selected_mask,
                         MaskOperationType::MainPointRemoved(index as usize, x, y),
                     ));
 
                     let Window = ww.unwrap();
-                    Window.set_mask_points(d.masks[0].into_rc_model());
-                    Window.set_bezier_control_points(d.masks[0].get_control_points_model_rc());
+                    Window.set_mask_points(d.current_mask().into_rc_model());
+                    Window
+                        .set_bezier_control_points(d.current_mask().get_control_points_model_rc());
                     #[cfg(not(debug_assertions))]
                     {
                         Window.set_connection_line_points(
-                            d.masks[0].generate_line_for_slint(Some(width), Some(height)),
+                            d.current_mask_mut()
+                                .generate_line_for_slint(Some(width), Some(height)),
                         );
                         Window.set_control_point_connection_line(
-                            d.masks[0].generate_control_point_connection_lines_for_slint(),
+                            d.current_mask()
+                                .generate_control_point_connection_lines_for_slint(),
                         );
                     }
+
+                    if d.current_mask_is_rendered() {
+                        update_preview(&Window, &mut d);
+                    }
                 }
                 Err(err) => {
                     println!("{:?}", err);
@@ -252,25 +276,32 @@ pub fn init_mask_callbacks<P>(
         .global::<MaskCallbacks>()
         .on_close_mask_path(move |width: f32, height: f32| {
             let mut d = d_w.lock().unwrap();
-            if !d.masks[0].is_closed() {
-                d.masks[0].close();
+            if !d.current_mask().is_closed() {
+                d.current_mask_mut().close();
 
-                hw.lock()
-                    .unwrap()
-                    .register_Mask_Operation_without_saving(&(0, MaskOperationType::MaskClosed()));
+                hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                    d.selected_mask,
+                    MaskOperationType::MaskClosed(),
+                ));
 
                 let Window = ww.unwrap();
-                Window.set_mask_points(d.masks[0].into_rc_model());
-                Window.set_bezier_control_points(d.masks[0].get_control_points_model_rc());
+                Window.set_mask_points(d.current_mask().into_rc_model());
+                Window.set_bezier_control_points(d.current_mask().get_control_points_model_rc());
                 #[cfg(not(debug_assertions))]
                 {
                     Window.set_connection_line_points(
-                        d.masks[0].generate_line_for_slint(Some(width), Some(height)),
+                        d.current_mask_mut()
+                            .generate_line_for_slint(Some(width), Some(height)),
                     );
                     Window.set_control_point_connection_line(
-                        d.masks[0].generate_control_point_connection_lines_for_slint(),
+                        d.current_mask()
+                            .generate_control_point_connection_lines_for_slint(),
                     );
                 }
+
+                if d.current_mask_is_rendered() {
+                    update_preview(&Window, &mut d);
+                }
             }
         });
 
@@ -280,11 +311,413 @@ pub fn init_mask_callbacks<P>(
         .global::<MaskCallbacks>()
         .on_update_history(move |index, x, y| {
             let data = dw.lock().unwrap();
-            let p = data.masks[0].get_point(index as usize);
+            let p = data.current_mask().get_handles()[index as usize];
+
+            let operation = if !data.current_mask().is_path() {
+                MaskOperationType::HandleMoved(index as usize, x, y, p[0], p[1])
+            } else {
+                MaskOperationType::MainPointMoved(index as usize, x, y, p[0], p[1])
+            };
+
+            hw.lock()
+                .unwrap()
+                .register_Mask_Operation_without_saving(&(data.selected_mask, operation))
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_add_gradient_mask(move |radial: bool| {
+            let mut data = dw.lock().unwrap();
+            let gradient = if radial {
+                GradientMask::radial()
+            } else {
+                GradientMask::linear()
+            };
+
+            data.masks.push(Mask::from_gradient(gradient));
+            data.selected_mask = data.masks.len() - 1;
 
             hw.lock().unwrap().register_Mask_Operation_without_saving(&(
-                0,
-                MaskOperationType::MainPointMoved(index as usize, x, y, p[0], p[1]),
-            ))
+                data.selected_mask,
+                MaskOperationType::GradientMaskAdded(gradient),
+            ));
+
+            let selected = data.selected_mask;
+            let Window = ww.unwrap();
+            show_mask(&Window, &mut data.masks, selected);
+
+            if data.mask_adjustments {
+                show_filters(&Window, data.current_mask().get_filters());
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_add_range_mask(move |color: bool| {
+            let mut data = dw.lock().unwrap();
+            let range = if color {
+                RangeMask::color()
+            } else {
+                RangeMask::luminance()
+            };
+
+            data.masks.push(Mask::from_range(range));
+            data.selected_mask = data.masks.len() - 1;
+
+            hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                data.selected_mask,
+                MaskOperationType::RangeMaskAdded(range),
+            ));
+
+            let selected = data.selected_mask;
+            let Window = ww.unwrap();
+            show_mask(&Window, &mut data.masks, selected);
+
+            if data.mask_adjustments {
+                show_filters(&Window, data.current_mask().get_filters());
+            }
         });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<MaskCallbacks>().on_add_brush_mask(move || {
+        let mut data = dw.lock().unwrap();
+        data.masks.push(Mask::brush());
+        data.selected_mask = data.masks.len() - 1;
+
+        hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+            data.selected_mask,
+            MaskOperationType::BrushMaskAdded(),
+        ));
+
+        let selected = data.selected_mask;
+        let Window = ww.unwrap();
+        show_mask(&Window, &mut data.masks, selected);
+
+        if data.mask_adjustments {
+            show_filters(&Window, data.current_mask().get_filters());
+        }
+    });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_begin_stroke(move |x: f32, y: f32| {
+            let mut data = dw.lock().unwrap();
+            let Window = ww.unwrap();
+            let callbacks = Window.global::<MaskCallbacks>();
+            data.current_mask_mut().begin_stroke(BrushStroke {
+                points: vec![[x, y]],
+                radius: callbacks.get_brush_size(),
+                flow: callbacks.get_brush_flow(),
+                hardness: callbacks.get_brush_hardness(),
+                erase: callbacks.get_brush_erase(),
+            });
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_extend_stroke(move |x: f32, y: f32| {
+            let mut data = dw.lock().unwrap();
+            data.current_mask_mut().extend_stroke([x, y]);
+            #[cfg(not(debug_assertions))]
+            ww.unwrap().set_connection_line_points(
+                data.current_mask_mut().generate_line_for_slint(None, None),
+            );
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<MaskCallbacks>().on_end_stroke(move || {
+        let mut data = dw.lock().unwrap();
+        let Some(stroke) = data.current_mask().last_stroke().cloned() else {
+            return;
+        };
+        hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+            data.selected_mask,
+            MaskOperationType::StrokeAdded(stroke),
+        ));
+
+        // the strokes are rendered only when they are complete
+        if data.current_mask_is_rendered() {
+            update_preview(&ww.unwrap(), &mut data);
+        }
+    });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_change_range(move |index: i32, value: f32| {
+            let mut data = dw.lock().unwrap();
+            if let Some(range) = data.current_mask_mut().range_mut() {
+                range.set_parameter(index as usize, value);
+            }
+
+            if data.current_mask_is_rendered() {
+                update_preview(&ww.unwrap(), &mut data);
+            }
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_update_range_history(move |index: i32, old: f32| {
+            let data = dw.lock().unwrap();
+            if let Some(&range) = data.current_mask().range() {
+                let mut previous = range;
+                previous.set_parameter(index as usize, old);
+                hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                    data.selected_mask,
+                    MaskOperationType::RangeChanged(previous, range),
+                ));
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_select_mask(move |index: i32| {
+            let mut data = dw.lock().unwrap();
+            if (index as usize) < data.masks.len() {
+                data.selected_mask = index as usize;
+            }
+            let selected = data.selected_mask;
+            let Window = ww.unwrap();
+            show_mask(&Window, &mut data.masks, selected);
+
+            if data.mask_adjustments {
+                show_filters(&Window, data.current_mask().get_filters());
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_adjust_mask(move |enabled: bool| {
+            let mut data = dw.lock().unwrap();
+            data.mask_adjustments = enabled;
+            show_filters(&ww.unwrap(), data.edited_filters());
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_change_feather(move |value: f32| {
+            let mut data = dw.lock().unwrap();
+            data.current_mask_mut().set_feather(value);
+            let selected = data.selected_mask;
+            let Window = ww.unwrap();
+            show_mask(&Window, &mut data.masks, selected);
+
+            if data.current_mask_is_rendered() {
+                update_preview(&Window, &mut data);
+            }
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_update_feather_history(move |old: f32| {
+            let data = dw.lock().unwrap();
+            if let Some(feather) = data.current_mask().feather() {
+                hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                    data.selected_mask,
+                    MaskOperationType::FeatherChanged(old, feather),
+                ));
+            }
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_change_density(move |value: f32| {
+            let mut data = dw.lock().unwrap();
+            data.current_mask_mut().set_density(value);
+
+            if data.current_mask_is_rendered() {
+                update_preview(&ww.unwrap(), &mut data);
+            }
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_update_density_history(move |old: f32| {
+            let data = dw.lock().unwrap();
+            hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                data.selected_mask,
+                MaskOperationType::DensityChanged(old, data.current_mask().density()),
+            ));
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_combine_mask(move |operation: i32, other: i32| {
+            let mut data = dw.lock().unwrap();
+            if other < 0 || other as usize >= data.masks.len() {
+                return;
+            }
+            let operation = match operation {
+                0 => MaskTreeOperation::Add,
+                1 => MaskTreeOperation::Subtract,
+                _ => MaskTreeOperation::Intersect,
+            };
+
+            let old = data.current_mask().get_combination().cloned();
+            let tree = old
+                .clone()
+                .unwrap_or(MaskTree::Mask(data.selected_mask))
+                .combine(operation, MaskTree::Mask(other as usize));
+            change_combination(&ww.unwrap(), &mut data, &hw, old, Some(tree));
+        });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window.global::<MaskCallbacks>().on_invert_mask(move || {
+        let mut data = dw.lock().unwrap();
+        let old = data.current_mask().get_combination().cloned();
+        let tree = old
+            .clone()
+            .unwrap_or(MaskTree::Mask(data.selected_mask))
+            .invert();
+
+        // a mask that selects only its own shape does not need a tree
+        let new = if tree == MaskTree::Mask(data.selected_mask) {
+            None
+        } else {
+            Some(tree)
+        };
+        change_combination(&ww.unwrap(), &mut data, &hw, old, new);
+    });
+
+    let ww = Window.as_weak();
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    Window
+        .global::<MaskCallbacks>()
+        .on_clear_combination(move || {
+            let mut data = dw.lock().unwrap();
+            let old = data.current_mask().get_combination().cloned();
+            if old.is_some() {
+                change_combination(&ww.unwrap(), &mut data, &hw, old, None);
+            }
+        });
+}
+
+// replaces the combination of the selected mask and stores the change in the history
+fn change_combination<P>(
+    Window: &LVIE,
+    data: &mut Data<P>,
+    history: &Mutex<History>,
+    old: Option<MaskTree>,
+    new: Option<MaskTree>,
+) where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    data.current_mask_mut().set_combination(new.clone());
+    history
+        .lock()
+        .unwrap()
+        .register_Mask_Operation_without_saving(&(
+            data.selected_mask,
+            MaskOperationType::CombinationChanged(old, new),
+        ));
+
+    let selected = data.selected_mask;
+    show_mask(Window, &mut data.masks, selected);
+    if data.current_mask().has_adjustments() {
+        update_preview(Window, data);
+    }
+}
+
+// loads the values of the filters into the sliders
+fn show_filters(Window: &LVIE, filters: &FilterArray) {
+    Window.invoke_update_values(slint::ModelRc::new(slint::VecModel::from(
+        filters.to_slider_values(),
+    )));
+}
+
+// shows the points and the outline of the selected mask on the image space
+pub fn show_mask(Window: &LVIE, masks: &mut [Mask], selected: usize) {
+    let mask = &mut masks[selected];
+    Window.set_mask_points(mask.into_rc_model());
+    Window.set_bezier_control_points(mask.get_control_points_model_rc());
+    #[cfg(not(debug_assertions))]
+    {
+        Window.set_connection_line_points(mask.generate_line_for_slint(None, None));
+        Window.set_control_point_connection_line(
+            mask.generate_control_point_connection_lines_for_slint(),
+        );
+    }
+
+    let callbacks = Window.global::<MaskCallbacks>();
+    callbacks.set_fixed_handles(!mask.is_path());
+    callbacks.set_editing_brush(*mask.get_kind() == MaskKind::Brush);
+    match mask.range() {
+        Some(&RangeMask::Luminance { low, high, falloff }) => {
+            callbacks.set_range_kind(1);
+            callbacks.set_range_low(low);
+            callbacks.set_range_high(high);
+            callbacks.set_range_falloff(falloff);
+        }
+        Some(&RangeMask::Color { tolerance, .. }) => {
+            callbacks.set_range_kind(2);
+            callbacks.set_range_tolerance(tolerance);
+        }
+        None => callbacks.set_range_kind(0),
+    }
+    callbacks.set_current_feather(mask.feather().unwrap_or(-1.0));
+    callbacks.set_current_density(mask.density());
+    let combination = mask.get_combination().cloned();
+
+    let names: Vec<slint::SharedString> = masks
+        .iter()
+        .enumerate()
+        .map(|(i, m)| format!("{} {}", m.name(), i + 1).into())
+        .collect();
+    callbacks.set_mask_names(slint::ModelRc::new(slint::VecModel::from(names)));
+    callbacks.set_combination(
+        combination
+            .map(|tree| tree.describe(masks))
+            .unwrap_or_default()
+            .into(),
+    );
+    callbacks.set_selected_mask(selected as i32);
 }
@@ -1,11 +1,51 @@
 mod curvecallbacks;
+mod geometrycallbacks;
 mod maskcallbacks;
 mod screencallbacks;
 mod settingscallbacks;
 mod toolbarcallbacks;
 
 pub use curvecallbacks::init_curve_callbacks;
-pub use maskcallbacks::init_mask_callbacks;
+pub use geometrycallbacks::{
+    init_geometry_callbacks, show_crop, show_lens, show_perspective, show_rotation,
+};
+pub use maskcallbacks::{init_mask_callbacks, show_mask};
 pub use screencallbacks::init_screen_callbacks;
 pub use settingscallbacks::init_settings_callbacks;
 pub use toolbarcallbacks::init_toolbar_callbacks;
+
+use slint::{Rgba8Pixel, SharedPixelBuffer};
+use LVIElib::traits::ScaleImage;
+
+use super::super::ui::LVIE;
+use super::Data;
+
+// renders the image with the current edits and loads it into the UI
+pub(crate) fn update_preview<P>(Window: &LVIE, data: &mut Data<P>)
+where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + LVIElib::traits::AsFloat
+        + num_traits::ToBytes,
+{
+    if data.image_dimensions() == (0, 0) {
+        return;
+    }
+
+    let img = data.update_image().scale_image::<P, image::Rgba<u8>>();
+
+    Window.set_image(slint::Image::from_rgba8(
+        SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(&img, img.width(), img.height()),
+    ));
+}
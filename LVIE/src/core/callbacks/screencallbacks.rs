@@ -1,19 +1,28 @@
 use std::sync::{Arc, Mutex};
 
-use crate::{
-    core::{FilterArray, FilterType},
-    history::{History, LogicOperationType},
-};
+use crate::core::{FilterArray, FilterType};
+use crate::history::{History, LogicOperationType, MaskOperationType};
+use crate::img_processing::collect_tone_histograms;
 
 use super::super::{
-    super::ui::{ScreenCallbacks, LVIE},
+    super::ui::{AlertType, ScreenCallbacks, LVIE},
+    processors::linear_colors,
     Data,
 };
-use slint::{ComponentHandle, Rgba8Pixel, SharedPixelBuffer, SharedString, Weak};
+use slint::{ComponentHandle, Model, Rgba8Pixel, SharedPixelBuffer, SharedString, Weak};
+use LVIElib::auto_tone::auto_tone;
 use LVIElib::traits::ScaleImage;
+use LVIElib::white_balance::{estimate_illuminant, neutralizing_white_point, AutoWhiteBalance};
 
 const INTERNAL_CLOCK_TIME: u64 = 2;
 
+// range of the temperature and the tint sliders
+const TEMP_RANGE: (f32, f32) = (4000.0, 8000.0);
+const TINT_RANGE: (f32, f32) = (-50.0, 50.0);
+
+// side of the square of pixels averaged by the eyedropper
+const EYEDROPPER_SIZE: u32 = 5;
+
 pub fn init_screen_callbacks<P>(
     Window: Weak<LVIE>,
     DATA: Arc<Mutex<Data<P>>>,
@@ -44,17 +53,30 @@ pub fn init_screen_callbacks<P>(
     Window.global::<ScreenCallbacks>().on_reset(move || {
         let mut data = data_weak.lock().expect("Failed to lock data");
 
-        hw.lock()
-            .unwrap()
-            .register_Logic_Operation_without_saving(&LogicOperationType::Reset(
-                data.get_loaded_filters().clone(),
-            ));
+        let img =
+            if data.mask_adjustments {
+                // only the adjustments of the selected mask are restored
+                hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                    data.selected_mask,
+                    MaskOperationType::FiltersChanged(
+                        data.current_mask().get_filters().clone(),
+                        FilterArray::new(None),
+                    ),
+                ));
+                data.current_mask_mut()
+                    .update_filters(FilterArray::new(None));
+                data.update_image().scale_image::<P, image::Rgba<u8>>()
+            } else {
+                hw.lock().unwrap().register_Logic_Operation_without_saving(
+                    &LogicOperationType::Reset(data.get_filters().clone()),
+                );
 
-        // restore filters
-        data.reset();
+                // restore filters
+                data.reset();
 
-        // restore all the previews to the original image
-        let img = data.full_res_preview.scale_image::<P, image::Rgba<u8>>();
+                // restore all the previews to the original image
+                data.full_res_preview.scale_image::<P, image::Rgba<u8>>()
+            };
 
         Window_weak
             .upgrade_in_event_loop(move |Window: LVIE| {
@@ -73,36 +95,25 @@ pub fn init_screen_callbacks<P>(
     let data_weak = DATA.clone();
     let Window_weak = Window.as_weak();
     let clock_w = CLOCK.clone();
-    Window.global::<ScreenCallbacks>().on_apply_filters(
-        move |exposition: f32,
-              box_blur: f32,
-              gaussian_blur: f32,
-              sharpening: f32,
-              temp: f32,
-              tint: f32,
-              saturation: f32| {
+    Window
+        .global::<ScreenCallbacks>()
+        .on_apply_filters(move |values: slint::ModelRc<f32>| {
             let mut data = data_weak.lock().expect("Failed to lock");
 
             if data.image_dimensions() == (0, 0) {
                 return;
             }
 
-            data.update_filter(FilterType::Exposition, vec![exposition]);
-            data.update_filter(FilterType::Saturation, vec![saturation]);
-            data.update_filter(FilterType::Sharpening, vec![sharpening, 5.0]);
-            data.update_filter(FilterType::Boxblur, vec![box_blur, 5.0]);
-            data.update_filter(FilterType::GaussianBlur, vec![gaussian_blur, 5.0]);
-            data.update_filter(
-                FilterType::WhiteBalance,
-                vec![2000f32 * temp + 6000f32, tint * 50.0],
-            );
+            let mut filters = data.edited_filters().clone();
+            filters.update_from_slider_values(&values.iter().collect::<Vec<f32>>());
+            data.update_edited_filters(filters);
 
             let processed = data.update_image().scale_image::<P, image::Rgba<u8>>();
 
             let pix_buf = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
                 &processed,
-                data.image_dimensions().0,
-                data.image_dimensions().1,
+                processed.width(),
+                processed.height(),
             );
 
             Window_weak
@@ -127,8 +138,7 @@ pub fn init_screen_callbacks<P>(
                     );
                 }
             }
-        },
-    );
+        });
 
     //save
     let data_weak = DATA.clone();
@@ -145,34 +155,207 @@ pub fn init_screen_callbacks<P>(
 
     let dw = DATA.clone();
     let hw = HISTORY.clone();
-    Window.global::<ScreenCallbacks>().on_update_history(
-        move |exposition: f32,
-              box_blur: f32,
-              gaussian_blur: f32,
-              sharpening: f32,
-              temp: f32,
-              tint: f32,
-              saturation: f32| {
+    Window
+        .global::<ScreenCallbacks>()
+        .on_update_history(move |values: slint::ModelRc<f32>| {
             let data = dw.lock().unwrap();
 
-            let filters = data.get_loaded_filters().clone();
+            let filters = data.edited_filters().clone();
 
-            let mut old_f = FilterArray::new(None);
+            let mut old_f = filters.clone();
+            old_f.update_from_slider_values(&values.iter().collect::<Vec<f32>>());
 
-            old_f.update_filter(FilterType::Exposition, vec![exposition]);
-            old_f.update_filter(FilterType::Saturation, vec![saturation]);
-            old_f.update_filter(FilterType::Sharpening, vec![sharpening, 5.0]);
-            old_f.update_filter(FilterType::Boxblur, vec![box_blur, 5.0]);
-            old_f.update_filter(FilterType::GaussianBlur, vec![gaussian_blur, 5.0]);
-            old_f.update_filter(
-                FilterType::WhiteBalance,
-                vec![2000f32 * temp + 6000f32, tint * 50.0],
-            );
+            if data.mask_adjustments {
+                hw.lock().unwrap().register_Mask_Operation_without_saving(&(
+                    data.selected_mask,
+                    MaskOperationType::FiltersChanged(old_f, filters),
+                ));
+            } else {
+                hw.lock()
+                    .unwrap()
+                    .register_Filter_Operation_and_save(&(old_f, filters), &data.full_res_preview)
+                    .expect("Failed to register filter operation");
+            }
+        });
 
-            hw.lock()
-                .unwrap()
-                .register_Filter_Operation_and_save(&(old_f, filters), &data.full_res_preview)
-                .expect("Failed to register filter operation");
-        },
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    let ww = Window.as_weak();
+    Window
+        .global::<ScreenCallbacks>()
+        .on_auto_white_balance(move |method: i32| {
+            let mut data = dw.lock().unwrap();
+            if data.image_dimensions() == (0, 0) {
+                return;
+            }
+
+            let method = match method {
+                1 => AutoWhiteBalance::WhitePatch,
+                2 => AutoWhiteBalance::Percentile(0.98),
+                _ => AutoWhiteBalance::GrayWorld,
+            };
+            // the white balance is rendered from the neutral white point
+            let illuminant = estimate_illuminant(&linear_colors(data.get_loaded_image()), method);
+            let neutral = FilterType::WhiteBalance.default();
+            let (temp, tint) = neutralizing_white_point(illuminant, neutral[0], neutral[1]);
+
+            let (filters, limited) = white_balanced(data.edited_filters(), temp, tint);
+            show_white_balance_limit(&ww.unwrap(), limited);
+            apply_edited_filters(ww.clone(), &mut data, &mut hw.lock().unwrap(), filters);
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    let ww = Window.as_weak();
+    Window
+        .global::<ScreenCallbacks>()
+        .on_pick_white_point(move |x: f32, y: f32| {
+            let mut data = dw.lock().unwrap();
+            if data.image_dimensions() == (0, 0) {
+                return;
+            }
+
+            // the pixels around the point before any edit, the other filters would change the
+            // color that has to become neutral
+            let patch = data.unedited_area(x, y, EYEDROPPER_SIZE);
+            let color = estimate_illuminant(&linear_colors(&patch), AutoWhiteBalance::GrayWorld);
+
+            // the white balance is rendered from the neutral white point
+            let neutral = FilterType::WhiteBalance.default();
+            let (temp, tint) = neutralizing_white_point(color, neutral[0], neutral[1]);
+
+            let (filters, limited) = white_balanced(data.edited_filters(), temp, tint);
+            show_white_balance_limit(&ww.unwrap(), limited);
+            apply_edited_filters(ww.clone(), &mut data, &mut hw.lock().unwrap(), filters);
+        });
+
+    let dw = DATA.clone();
+    let hw = HISTORY.clone();
+    let ww = Window.as_weak();
+    Window.global::<ScreenCallbacks>().on_auto_tone(move || {
+        let mut data = dw.lock().unwrap();
+        if data.image_dimensions() == (0, 0) {
+            return;
+        }
+
+        // the statistics of the image the filters receive, after the geometry and inside the crop
+        let (histogram, lightness) = collect_tone_histograms(&*data.unedited_crop());
+        let tone = auto_tone(&histogram, &lightness);
+
+        // every value goes in the filters of the sliders, so they can be changed and undone together
+        let mut filters = data.edited_filters().clone();
+        let pivot = filters.get_filter(FilterType::Contrast)[2];
+        filters.update_filter(FilterType::Exposition, vec![tone.exposure]);
+        filters.update_filter(FilterType::Contrast, vec![tone.contrast, 0.0, pivot]);
+        filters.update_filter(
+            FilterType::HighlightsShadows,
+            vec![tone.highlights, tone.shadows],
+        );
+        filters.update_filter(FilterType::WhitesBlacks, vec![tone.whites, tone.blacks]);
+
+        apply_edited_filters(ww.clone(), &mut data, &mut hw.lock().unwrap(), filters);
+    });
+}
+
+// the filters with the white balance set to `temp` and `tint`, limited to the range of the sliders.
+// The flag is true if the white point was out of the range, so the color is not fully neutral
+fn white_balanced(filters: &FilterArray, temp: f32, tint: f32) -> (FilterArray, bool) {
+    let limited = [
+        temp.clamp(TEMP_RANGE.0, TEMP_RANGE.1),
+        tint.clamp(TINT_RANGE.0, TINT_RANGE.1),
+    ];
+    let mut filters = filters.clone();
+    filters.update_filter(FilterType::WhiteBalance, limited.to_vec());
+    (filters, limited != [temp, tint])
+}
+
+// warns in the toolbar when the white balance could not reach the neutral color
+fn show_white_balance_limit(Window: &LVIE, limited: bool) {
+    if limited {
+        Window.set_AlertBoxType(AlertType::Warning);
+        Window.set_AlertText("The white balance is out of the range of the sliders".into());
+    } else {
+        Window.set_AlertBoxType(AlertType::Null);
+        Window.set_AlertText("".into());
+    }
+}
+
+// replaces the edited filters as a single history operation, then updates the preview and the sliders
+fn apply_edited_filters<P>(
+    Window: Weak<LVIE>,
+    data: &mut Data<P>,
+    history: &mut History,
+    filters: FilterArray,
+) where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + num_traits::ToBytes
+        + LVIElib::traits::AsFloat,
+{
+    let old = data.edited_filters().clone();
+    data.update_edited_filters(filters.clone());
+    let processed = data.update_image().scale_image::<P, image::Rgba<u8>>();
+
+    // the history stores the preview with the new filters, like after a slider is released
+    if data.mask_adjustments {
+        history.register_Mask_Operation_without_saving(&(
+            data.selected_mask,
+            MaskOperationType::FiltersChanged(old, filters.clone()),
+        ));
+    } else {
+        history
+            .register_Filter_Operation_and_save(&(old, filters.clone()), &data.full_res_preview)
+            .expect("Failed to register filter operation");
+    }
+
+    let pix_buf = SharedPixelBuffer::<Rgba8Pixel>::clone_from_slice(
+        &processed,
+        processed.width(),
+        processed.height(),
     );
+    let values = filters.to_slider_values();
+
+    Window
+        .upgrade_in_event_loop(move |Window: LVIE| {
+            Window.set_image(slint::Image::from_rgba8(pix_buf));
+            Window.invoke_update_values(slint::ModelRc::new(slint::VecModel::from(values)));
+        })
+        .expect("Failed to call event loop");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn white_point_out_of_range_is_limited() {
+        let neutral = FilterType::WhiteBalance.default();
+        let filters = FilterArray::new(None);
+
+        // a candle light is warmer than the warmest white point of the slider
+        let (temp, tint) = neutralizing_white_point([1.0, 0.45, 0.1], neutral[0], neutral[1]);
+        assert!(temp < TEMP_RANGE.0);
+        let (limited, is_limited) = white_balanced(&filters, temp, tint);
+        assert!(is_limited);
+        assert_eq!(
+            limited.get_filter(FilterType::WhiteBalance)[0],
+            TEMP_RANGE.0
+        );
+
+        // a gray patch is already neutral
+        let (temp, tint) = neutralizing_white_point([0.5, 0.5, 0.5], neutral[0], neutral[1]);
+        let (_, is_limited) = white_balanced(&filters, temp, tint);
+        assert!(!is_limited);
+    }
 }
@@ -3,6 +3,7 @@ use std::{
     thread,
 };
 
+use super::{show_crop, show_lens, show_mask, show_perspective, show_rotation};
 use crate::history::{GeometricOperationType, History};
 
 use super::super::{
@@ -14,7 +15,7 @@ use itertools::{max, Itertools};
 use slint::{ComponentHandle, Rgba8Pixel, SharedPixelBuffer, Weak};
 use LVIElib::traits::ScaleImage;
 
-use crate::core::CRgbaImage;
+use crate::core::{CRgbaImage, Crop, LensModel, Perspective, Rotation};
 use crate::raw_decoder::*;
 
 use crate::img_processing::collect_histogram_data;
@@ -186,6 +187,15 @@ pub fn init_toolbar_callbacks<P>(
 
         // load the image
         data.load_image(img.scale_image::<image::Rgba<u8>, P>(), true);
+        // the geometry of the previous image does not make sense anymore
+        data.crop = Crop::default();
+        data.lens = LensModel::default();
+        data.rotation = Rotation::default();
+        data.perspective = Perspective::default();
+        show_crop(&Window_weak.unwrap(), &data.crop);
+        show_lens(&Window_weak.unwrap(), &data.lens);
+        show_rotation(&Window_weak.unwrap(), &data.rotation);
+        show_perspective(&Window_weak.unwrap(), &data.perspective);
 
         hw.lock()
             .unwrap()
@@ -225,14 +235,19 @@ pub fn init_toolbar_callbacks<P>(
         .global::<ToolbarCallbacks>()
         .on_rotate_90_deg(move || {
             let mut data = data_weak.lock().unwrap();
-            data.rotation += 90.0;
             let mut history = hw.lock().unwrap();
 
-            let img = image::imageops::rotate90(&data.full_res_preview);
-            // load the image
-            data.load_image(img.clone(), false);
+            // the rotation is the first stage of the rendering, the filters are rendered again
+            data.rotation = data.rotation.turned(true);
+            data.crop = data.crop.rotated_90(true);
+            data.perspective = data.perspective.rotated_90(true);
+            data.masks.iter_mut().for_each(|m| m.rotate_90(true));
+            show_crop(&Window_weak.unwrap(), &data.crop);
+            show_perspective(&Window_weak.unwrap(), &data.perspective);
+            let selected = data.selected_mask;
+            show_mask(&Window_weak.unwrap(), &mut data.masks, selected);
 
-            let img = img.scale_image::<P, image::Rgba<u8>>();
+            let img = data.update_image().scale_image::<P, image::Rgba<u8>>();
 
             history
                 .register_Geometric_Operation_and_save(
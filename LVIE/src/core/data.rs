@@ -1,6 +1,6 @@
 use std::fmt::Debug;
 
-use image::{Pixel, Primitive};
+use image::{Pixel, Primitive, SubImage};
 use num_traits::ToBytes;
 use LVIE_GPU::Pod;
 
@@ -11,6 +11,7 @@ use super::filters::*;
 use super::rendering::*;
 use super::ImageBuffers;
 
+use super::geometry::{Crop, Geometry, LensModel, Perspective, Rotation};
 use super::masks::Mask;
 
 #[derive(Debug)]
@@ -22,11 +23,23 @@ where
     rendering: Rendering<P>,
     pub full_res_preview: CRgbaImage<P>,
     filters: FilterArray,
-    loaded_filters: FilterArray,
     loaded_image: CRgbaImage<P>,
-    pub curve: Curve,
+    pub curves: ToneCurves,
+    // the curve edited in the UI
+    pub curve_channel: CurveChannel,
     pub masks: Vec<Mask>,
-    pub rotation: f32,
+    // the mask edited in the image space
+    pub selected_mask: usize,
+    // true if the sliders edit the filters of the selected mask
+    pub mask_adjustments: bool,
+    pub lens: LensModel,
+    pub rotation: Rotation,
+    pub perspective: Perspective,
+    pub crop: Crop,
+    // true while the crop or the masks are edited, the preview shows the whole image
+    pub uncropped_preview: bool,
+    // true while the corners of the perspective are moved, the preview is not corrected
+    pub unwarped_preview: bool,
 }
 
 impl<P> Data<P>
@@ -47,17 +60,24 @@ where
             }
         };
         let mut imagebuffers = ImageBuffers::from_rgb(img.clone());
-        imagebuffers.set_updates(true, true);
+        imagebuffers.set_updates(true, true, true);
 
         let mut data = Data {
             rendering,
             full_res_preview: img.clone(),
             filters: FilterArray::new(filters_to_load),
-            loaded_filters: FilterArray::new(None),
             loaded_image: img,
-            curve: Curve::new(CurveType::MONOTONE),
+            curves: ToneCurves::new(),
+            curve_channel: CurveChannel::Luma,
             masks: vec![Mask::new()],
-            rotation: 0.0,
+            selected_mask: 0,
+            mask_adjustments: false,
+            lens: LensModel::default(),
+            rotation: Rotation::default(),
+            perspective: Perspective::default(),
+            crop: Crop::default(),
+            uncropped_preview: false,
+            unwarped_preview: false,
         };
 
         data.rendering.attach_image_buffers(imagebuffers);
@@ -65,12 +85,41 @@ where
         data
     }
 
-    pub fn get_loaded_filters(&self) -> &FilterArray {
-        &self.loaded_filters
+    pub fn get_filters(&self) -> &FilterArray {
+        &self.filters
     }
 
-    pub fn manual_reset_rendering(&mut self) {
-        self.rendering.imagebuffers.set_updated(true, false, false);
+    pub fn current_mask(&self) -> &Mask {
+        &self.masks[self.selected_mask]
+    }
+
+    pub fn current_mask_mut(&mut self) -> &mut Mask {
+        &mut self.masks[self.selected_mask]
+    }
+
+    // true if the shape of the selected mask changes the image,
+    // directly or as a part of the combination of another mask
+    pub fn current_mask_is_rendered(&self) -> bool {
+        let selected = self.selected_mask;
+        self.masks.iter().enumerate().any(|(i, mask)| {
+            mask.has_adjustments()
+                && match mask.get_combination() {
+                    Some(tree) => tree.uses(selected),
+                    None => i == selected,
+                }
+        })
+    }
+
+    pub fn selected_curve(&self) -> &Curve {
+        self.curves.get(self.curve_channel)
+    }
+
+    pub fn selected_curve_mut(&mut self) -> &mut Curve {
+        self.curves.get_mut(self.curve_channel)
+    }
+
+    pub fn get_loaded_image(&self) -> &CRgbaImage<P> {
+        &self.loaded_image
     }
 
     pub fn update_all_color_spaces(&mut self) {
@@ -81,60 +130,116 @@ where
         self.loaded_image.dimensions()
     }
 
+    // the geometry rendered in the preview or in the exported image
+    fn geometry(&self, preview: bool) -> Geometry {
+        Geometry {
+            lens: self.lens,
+            rotation: self.rotation,
+            perspective: if preview && self.unwarped_preview {
+                Perspective::default()
+            } else {
+                self.perspective
+            },
+            crop: self.crop,
+            cropped: !preview || !self.uncropped_preview,
+        }
+    }
+
+    // the loaded image with only the geometry of the preview, before the crop. It is taken
+    // from the rendering cache, the preview is rendered again only if the cache is outdated
+    pub fn unedited_preview(&mut self) -> &CRgbaImage<P> {
+        let geometry = self.geometry(true);
+        if self
+            .rendering
+            .geometric_output(&self.loaded_image, &geometry)
+            .is_none()
+        {
+            self.update_image();
+        }
+        self.rendering
+            .geometric_output(&self.loaded_image, &geometry)
+            .unwrap_or(&self.loaded_image)
+    }
+
+    // the pixels of the unedited preview in the square of side `size` centered in the point
+    // (`x`, `y`), in percent of the preview as it is shown
+    pub fn unedited_area(&mut self, x: f32, y: f32, size: u32) -> CRgbaImage<P> {
+        let geometry = self.geometry(true);
+        let img = self.unedited_preview();
+        let (width, height) = img.dimensions();
+        let (left, top, w, h) = if geometry.cropped {
+            geometry.crop.to_pixels(width, height)
+        } else {
+            (0, 0, width, height)
+        };
+
+        let (cx, cy) = (
+            left + ((x / 100.0 * w as f32) as u32).min(w - 1),
+            top + ((y / 100.0 * h as f32) as u32).min(h - 1),
+        );
+        let (x0, y0) = (cx.saturating_sub(size / 2), cy.saturating_sub(size / 2));
+        image::imageops::crop_imm(img, x0, y0, size.min(width - x0), size.min(height - y0))
+            .to_image()
+    }
+
+    // the part of the unedited preview inside the crop, the statistics of the image are taken from it
+    pub fn unedited_crop(&mut self) -> SubImage<&CRgbaImage<P>> {
+        let crop = self.crop;
+        let img = self.unedited_preview();
+        let (x, y, width, height) = crop.to_pixels(img.width(), img.height());
+        image::imageops::crop_imm(img, x, y, width, height)
+    }
+
+    // size of the loaded image after the rotation, the one the crop and the masks refer to
+    pub fn rotated_dimensions(&self) -> (u32, u32) {
+        let (width, height) = self.loaded_image.dimensions();
+        self.rotation.dimensions(width, height)
+    }
+
     pub fn load_image(&mut self, img: CRgbaImage<P>, update: bool) {
         self.loaded_image = img.clone();
+        self.rendering.clear_cache();
         self.rendering.imagebuffers.replace_rgb(img.clone());
         if update {
             self.rendering.imagebuffers.update();
         }
         self.full_res_preview = img;
-        self.loaded_filters = FilterArray::new(None);
     }
 
     pub fn update_filters(&mut self, filters: FilterArray) {
         self.filters = filters;
     }
 
-    pub fn norm_filters(&mut self) {
-        let mut filters = &self.filters - &self.loaded_filters;
-        filters.update_filter(
-            FilterType::WhiteBalance,
-            self.loaded_filters
-                .get_filter(FilterType::WhiteBalance)
-                .clone()
-                .into_iter()
-                .chain(
-                    filters
-                        .get_filter(FilterType::WhiteBalance)
-                        .clone()
-                        .into_iter(),
-                )
-                .collect(),
-        );
-        self.loaded_filters = &self.loaded_filters + &filters;
+    // the filters controlled by the sliders: the global ones or the ones of the selected mask
+    pub fn edited_filters(&self) -> &FilterArray {
+        if self.mask_adjustments {
+            self.current_mask().get_filters()
+        } else {
+            &self.filters
+        }
     }
 
+    pub fn update_edited_filters(&mut self, filters: FilterArray) {
+        if self.mask_adjustments {
+            self.current_mask_mut().update_filters(filters);
+        } else {
+            self.filters = filters;
+        }
+    }
+
+    // renders the whole pipeline starting from the loaded image,
+    // the stages that did not change are taken from the rendering cache
     pub fn update_image(&mut self) -> CRgbaImage<P> {
-        let mut filters = &self.filters - &self.loaded_filters;
-        filters.update_filter(
-            FilterType::WhiteBalance,
-            self.loaded_filters
-                .get_filter(FilterType::WhiteBalance)
-                .clone()
-                .into_iter()
-                .chain(
-                    filters
-                        .get_filter(FilterType::WhiteBalance)
-                        .clone()
-                        .into_iter(),
-                )
-                .collect(),
-        );
         self.full_res_preview = self
             .rendering
-            .render_data(&(self.full_res_preview), &filters)
+            .render_data(
+                &self.loaded_image,
+                &self.geometry(true),
+                &self.filters,
+                &self.curves,
+                &self.masks,
+            )
             .unwrap();
-        self.loaded_filters = &self.loaded_filters + &filters;
         self.full_res_preview.clone()
     }
 
@@ -145,33 +250,35 @@ where
     pub fn reset(&mut self) {
         self.full_res_preview = self.loaded_image.clone();
         self.filters = FilterArray::new(None);
-        self.loaded_filters = FilterArray::new(None);
+        self.rendering.clear_cache();
         self.rendering.imagebuffers.reset();
         self.rendering
             .imagebuffers
             .replace_rgb(self.full_res_preview.clone());
         self.rendering.imagebuffers.update();
+
+        // the tone curves, the local adjustments and the geometry are kept
+        if !self.curves.is_identity()
+            || self.masks.iter().any(|m| m.has_adjustments())
+            || !self.crop.is_full()
+            || !self.lens.is_identity()
+            || !self.rotation.is_identity()
+            || !self.perspective.is_identity()
+        {
+            self.update_image();
+        }
     }
 
     pub fn export(&mut self) -> CRgbaImage<image::Rgba<u8>> {
-        let mut filters = self.filters.clone();
-        filters.update_filter(
-            FilterType::WhiteBalance,
-            vec![6500.0, 0.0]
-                .into_iter()
-                .chain(
-                    filters
-                        .get_filter(FilterType::WhiteBalance)
-                        .clone()
-                        .into_iter(),
-                )
-                .collect(),
-        );
-        println!("{:?}", filters);
-        return self
-            .rendering
-            .render_data(&self.full_res_preview, &filters)
+        self.rendering
+            .render_data(
+                &self.loaded_image,
+                &self.geometry(false),
+                &self.filters,
+                &self.curves,
+                &self.masks,
+            )
             .unwrap()
-            .scale_image::<P, image::Rgba<u8>>();
+            .scale_image::<P, image::Rgba<u8>>()
     }
 }
@@ -1,12 +1,17 @@
 use std::fmt::Debug;
 
+use LVIElib::channel_mixer::PARAMETERS as CHANNEL_MIXER_PARAMETERS;
+use LVIElib::color_grading::{PARAMETERS as COLOR_GRADING_PARAMETERS, ZONES};
+use LVIElib::hsl_mixer::HUE_BANDS;
+use LVIElib::wavelet_denoise::WAVELET_SCALES;
+
 #[derive(Debug, PartialEq, Clone, Copy)]
 pub enum CurveType {
     MONOTONE,
     SMOOTH,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Curve {
     xs: Vec<f32>,
     ys: Vec<f32>,
@@ -164,39 +169,251 @@ impl Curve {
     pub fn get_curve_type(&self) -> &CurveType {
         &self.curve_type
     }
+
+    // true if the control points are the default ones or lie on the diagonal between them,
+    // every spline through them is the identity
+    pub fn is_identity(&self) -> bool {
+        self.xs.first() == Some(&0.0)
+            && self.xs.last() == Some(&100.0)
+            && self.xs.iter().zip(&self.ys).all(|(x, y)| x == y)
+    }
+
+    // samples the curve in `size` evenly spaced points, input and output are normalized in 0..1
+    pub fn bake_lut(&self, size: usize) -> Vec<f32> {
+        let (last_x, last_y) = (*self.xs.last().unwrap(), *self.ys.last().unwrap());
+        (0..size)
+            .map(|i| {
+                let x = 100.0 * i as f32 / (size - 1) as f32;
+                // the spline is not defined after the last point
+                let y = if x >= last_x {
+                    last_y
+                } else {
+                    self.apply_curve(x)
+                };
+                (y / 100.0).clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
+
+pub const CURVE_LUT_SIZE: usize = 4096;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CurveChannel {
+    Luma,
+    Red,
+    Green,
+    Blue,
+}
+
+impl CurveChannel {
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn from_index(index: usize) -> Option<CurveChannel> {
+        match index {
+            0 => Some(CurveChannel::Luma),
+            1 => Some(CurveChannel::Red),
+            2 => Some(CurveChannel::Green),
+            3 => Some(CurveChannel::Blue),
+            _ => None,
+        }
+    }
+}
+
+// one curve for every `CurveChannel`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToneCurves {
+    curves: [Curve; 4],
+}
+
+impl ToneCurves {
+    pub fn new() -> ToneCurves {
+        ToneCurves {
+            curves: std::array::from_fn(|_| Curve::new(CurveType::MONOTONE)),
+        }
+    }
+
+    pub fn get(&self, channel: CurveChannel) -> &Curve {
+        &self.curves[channel.index()]
+    }
+
+    pub fn get_mut(&mut self, channel: CurveChannel) -> &mut Curve {
+        &mut self.curves[channel.index()]
+    }
+
+    // the luts are indexed by `CurveChannel`
+    pub fn bake_luts(&self) -> [Vec<f32>; 4] {
+        std::array::from_fn(|i| self.curves[i].bake_lut(CURVE_LUT_SIZE))
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.curves.iter().all(|curve| curve.is_identity())
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Filter {
     pub filtertype: FilterType,
     pub parameters: Vec<f32>,
 }
 
+impl Filter {
+    // true if applying the filter would leave the image unchanged
+    pub fn is_default(&self) -> bool {
+        let default = self.filtertype.default();
+        match self.filtertype {
+            FilterType::WhiteBalance => self.parameters[..2] == default[..2],
+            FilterType::WaveletDenoise => self.parameters[..2] == default[..2],
+            // preserving the luminosity does nothing with the identity matrix
+            FilterType::ChannelMixer => self.parameters[..12] == default[..12],
+            // without chroma and luminance the hues, the balance and the blending do nothing
+            FilterType::ColorGrading => self.parameters[..3 * ZONES]
+                .chunks(3)
+                .all(|zone| zone[1] == 0.0 && zone[2] == 0.0),
+            FilterType::HslMixer
+            | FilterType::HighlightsShadows
+            | FilterType::WhitesBlacks
+            | FilterType::LocalContrast => self.parameters == default,
+            _ => self.parameters[0] == default[0],
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum FilterType {
     Exposition,
+    WaveletDenoise,
     Sharpening,
     WhiteBalance,
+    Dehaze,
     Contrast,
+    HighlightsShadows,
+    WhitesBlacks,
+    LocalContrast,
     Saturation,
+    HslMixer,
+    ChannelMixer,
+    BlackAndWhite,
+    ColorGrading,
     GaussianBlur,
     Boxblur,
+    Grain,
+    Vignette,
 }
 
 impl FilterType {
+    // every filter in the order of application
+    pub const ALL: [FilterType; 18] = [
+        FilterType::Exposition,
+        FilterType::WaveletDenoise,
+        FilterType::Sharpening,
+        FilterType::WhiteBalance,
+        FilterType::Dehaze,
+        FilterType::Contrast,
+        FilterType::HighlightsShadows,
+        FilterType::WhitesBlacks,
+        FilterType::LocalContrast,
+        FilterType::Saturation,
+        FilterType::HslMixer,
+        FilterType::ChannelMixer,
+        FilterType::BlackAndWhite,
+        FilterType::ColorGrading,
+        FilterType::GaussianBlur,
+        FilterType::Boxblur,
+        FilterType::Grain,
+        FilterType::Vignette,
+    ];
+
     pub fn default(&self) -> Vec<f32> {
-        if *self == FilterType::WhiteBalance {
-            return vec![6000.0, 0.0];
+        match self {
+            FilterType::WhiteBalance => vec![6000.0, 0.0],
+            FilterType::HslMixer => vec![0.0; 3 * HUE_BANDS],
+            FilterType::ChannelMixer => {
+                // the identity matrix
+                let mut parameters = vec![0.0; CHANNEL_MIXER_PARAMETERS];
+                for c in 0..3 {
+                    parameters[4 * c] = 1.0;
+                }
+                parameters
+            }
+            FilterType::BlackAndWhite => vec![0.0; 1 + HUE_BANDS],
+            FilterType::ColorGrading => {
+                let mut parameters = vec![0.0; COLOR_GRADING_PARAMETERS];
+                parameters[COLOR_GRADING_PARAMETERS - 1] = 0.5;
+                parameters
+            }
+            FilterType::WaveletDenoise => {
+                let mut parameters = vec![0.0, 0.0, 1.0];
+                parameters.extend([1.0; WAVELET_SCALES]);
+                parameters
+            }
+            FilterType::Grain => vec![0.0, 0.5, 0.5, 0.0],
+            FilterType::Vignette => vec![0.0, 0.5, 0.0, 0.5],
+            FilterType::Contrast => vec![0.0, 0.0, 0.5],
+            // the size of the kernel is fixed in the UI
+            FilterType::Sharpening | FilterType::GaussianBlur | FilterType::Boxblur => {
+                vec![0.0, 5.0]
+            }
+            FilterType::Exposition | FilterType::Saturation | FilterType::Dehaze => vec![0.0],
+            _ => vec![0.0, 0.0],
         }
-        vec![0.0, 0.0]
     }
 
     pub fn index(&self) -> usize {
         *self as usize
     }
+
+    // number of values of the filter in the sliders of the UI
+    pub fn slider_len(&self) -> usize {
+        match self {
+            FilterType::Sharpening | FilterType::GaussianBlur | FilterType::Boxblur => 1,
+            _ => self.default().len(),
+        }
+    }
+
+    // the sliders hold the values of every filter one after the other in the order of
+    // application, the same order of the `filter-values` property of the screen
+    pub fn slider_offset(&self) -> usize {
+        FilterType::ALL[..self.index()]
+            .iter()
+            .map(|filtertype| filtertype.slider_len())
+            .sum()
+    }
+
+    pub fn slider_values_len() -> usize {
+        FilterType::ALL
+            .iter()
+            .map(|filtertype| filtertype.slider_len())
+            .sum()
+    }
+
+    // temperature and tint have their own scale in the sliders
+    pub fn to_slider_values(&self, parameters: &[f32]) -> Vec<f32> {
+        match self {
+            FilterType::WhiteBalance => {
+                vec![(parameters[0] - 6000f32) / 2000f32, parameters[1] / 50f32]
+            }
+            _ => parameters[..self.slider_len()].to_vec(),
+        }
+    }
+
+    // inverse of `to_slider_values`
+    pub fn from_slider_values(&self, values: &[f32]) -> Vec<f32> {
+        match self {
+            FilterType::WhiteBalance => vec![2000f32 * values[0] + 6000f32, values[1] * 50f32],
+            FilterType::Sharpening | FilterType::GaussianBlur | FilterType::Boxblur => {
+                let mut parameters = values.to_vec();
+                parameters.extend(&self.default()[values.len()..]);
+                parameters
+            }
+            _ => values.to_vec(),
+        }
+    }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 // Struct to handle the application of filters
 // it has an order of application of the filters
 pub struct FilterArray {
@@ -227,12 +444,36 @@ impl FilterArray {
     pub fn new(filters: Option<Vec<Filter>>) -> FilterArray {
         let mut fa = vec![
             filter!(FilterType::Exposition, 0.0),
+            // luma and chroma strength, threshold and the strength of every wavelet scale
+            filter!(FilterType::WaveletDenoise),
             filter!(FilterType::Sharpening),
             filter!(FilterType::WhiteBalance),
-            filter!(FilterType::Contrast, 0.0),
+            // strength, negative values add haze
+            filter!(FilterType::Dehaze),
+            // amount, algorithm (linear, histogram equalization, s-curve) and pivot
+            filter!(FilterType::Contrast),
+            // highlights and shadows
+            filter!(FilterType::HighlightsShadows),
+            // whites and blacks
+            filter!(FilterType::WhitesBlacks),
+            // clarity and texture
+            filter!(FilterType::LocalContrast),
             filter!(FilterType::Saturation, 0.0),
+            // hue shift, saturation and luminance of every hue band
+            filter!(FilterType::HslMixer),
+            // the rows of the matrix in linear light, the offsets and the preserve luminosity flag
+            filter!(FilterType::ChannelMixer),
+            // enabled and the luminance of every hue band
+            filter!(FilterType::BlackAndWhite),
+            // hue, chroma and luminance of shadows, midtones, highlights and global,
+            // then balance and blending
+            filter!(FilterType::ColorGrading),
             filter!(FilterType::GaussianBlur),
             filter!(FilterType::Boxblur),
+            // amount, size, roughness and the seed of the noise
+            filter!(FilterType::Grain),
+            // amount, midpoint, roundness and feather, rendered after the crop
+            filter!(FilterType::Vignette),
         ];
 
         if filters.is_some() {
@@ -251,6 +492,32 @@ impl FilterArray {
     pub fn get_filter(&self, filtertype: FilterType) -> &Vec<f32> {
         &self.filters[filtertype.index()].parameters
     }
+
+    // updates the filters from the values of the sliders of the UI,
+    // see `FilterType::slider_offset` for their order
+    pub fn update_from_slider_values(&mut self, values: &[f32]) {
+        assert_eq!(values.len(), FilterType::slider_values_len());
+        for filtertype in FilterType::ALL {
+            let offset = filtertype.slider_offset();
+            let parameters =
+                filtertype.from_slider_values(&values[offset..offset + filtertype.slider_len()]);
+            self.update_filter(filtertype, parameters);
+        }
+    }
+
+    // inverse of `update_from_slider_values`
+    pub fn to_slider_values(&self) -> Vec<f32> {
+        FilterType::ALL
+            .iter()
+            .flat_map(|filtertype| filtertype.to_slider_values(self.get_filter(*filtertype)))
+            .collect()
+    }
+}
+
+impl Default for FilterArray {
+    fn default() -> Self {
+        FilterArray::new(None)
+    }
 }
 
 impl IntoIterator for FilterArray {
@@ -271,59 +538,80 @@ impl<'a> IntoIterator for &'a FilterArray {
     }
 }
 
-impl std::ops::Sub for &FilterArray {
-    type Output = FilterArray;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the slint file with the `filter-values` and `update-values` of the sliders
+    const SCREEN: &str = include_str!("../../ui/widgets/screen.slint");
+
+    // the text between `start` and the first `end` after it
+    fn between<'a>(text: &'a str, start: &str, end: &str) -> &'a str {
+        let from = text.find(start).unwrap() + start.len();
+        &text[from..from + text[from..].find(end).unwrap()]
+    }
 
-    fn sub(self, rhs: Self) -> Self::Output {
-        let mut out = FilterArray::new(Some(self.filters.clone()));
+    #[test]
+    fn all_follows_the_enum() {
+        for (i, filtertype) in FilterType::ALL.iter().enumerate() {
+            assert_eq!(filtertype.index(), i);
+        }
+    }
 
-        // Exposition
-        // difference between expositions
-        out.filters[0].parameters[0] -= rhs.filters[0].parameters[0];
-        // Sharpening
-        // difference between the amounts
-        out.filters[1].parameters[0] -= rhs.filters[1].parameters[0];
-        // Contrast
-        // difference in contrast
-        out.filters[3].parameters[0] -= rhs.filters[3].parameters[0];
-        // Saturation
-        // difference in saturation
-        out.filters[4].parameters[0] -= rhs.filters[4].parameters[0];
+    #[test]
+    fn slider_values_round_trip() {
+        let mut filters = FilterArray::default();
+        for filtertype in FilterType::ALL {
+            // values that are different for every filter and every position
+            let mut parameters = filtertype.default();
+            for (i, p) in parameters[..filtertype.slider_len()].iter_mut().enumerate() {
+                *p = (filtertype.index() * 100 + i) as f32 / 1000.0;
+            }
+            if filtertype == FilterType::WhiteBalance {
+                parameters = vec![6500.0, 25.0];
+            }
+            filters.update_filter(filtertype, parameters);
+        }
 
-        // Gaussian Blur and box blur cannot be trasformed
-        out.filters[5].parameters[0] -= rhs.filters[5].parameters[0];
-        out.filters[6].parameters[0] -= rhs.filters[6].parameters[0];
+        let values = filters.to_slider_values();
+        assert_eq!(values.len(), FilterType::slider_values_len());
 
-        out
+        let mut restored = FilterArray::default();
+        restored.update_from_slider_values(&values);
+        assert_eq!(restored, filters);
+    }
+
+    #[test]
+    fn default_filters_round_trip() {
+        let mut restored = FilterArray::default();
+        restored.update_from_slider_values(&FilterArray::default().to_slider_values());
+        assert_eq!(restored, FilterArray::default());
     }
-}
 
-impl std::ops::Add for &FilterArray {
-    type Output = FilterArray;
-
-    fn add(self, rhs: Self) -> Self::Output {
-        let mut out = FilterArray::new(Some(self.filters.clone()));
-
-        // Exposition
-        // difference between expositions
-        out.filters[0].parameters[0] += rhs.filters[0].parameters[0];
-        // Sharpening
-        // difference between the amounts
-        out.filters[1].parameters[0] += rhs.filters[1].parameters[0];
-        // White balance
-        // copy the values from the other
-        out.filters[2].parameters = rhs.filters[2].parameters[2..=3].to_vec();
-        // Contrast
-        // difference in contrast
-        out.filters[3].parameters[0] += rhs.filters[3].parameters[0];
-        // Saturation
-        // difference in saturation
-        out.filters[4].parameters[0] += rhs.filters[4].parameters[0];
-
-        // Gaussian Blur and box blur cannot be trasformed
-        out.filters[5].parameters[0] += rhs.filters[5].parameters[0];
-        out.filters[6].parameters[0] += rhs.filters[6].parameters[0];
-
-        out
+    #[test]
+    fn slint_uses_every_slider_value() {
+        let filter_values = between(SCREEN, "property <[float]> filter-values: [", "];");
+        let count = filter_values
+            .split(',')
+            .filter(|v| !v.trim().is_empty())
+            .count();
+        assert_eq!(count, FilterType::slider_values_len());
+
+        // `update-values` reads every value once
+        let update_values = between(
+            SCREEN,
+            "update-values(values) => {",
+            "_last_filter = values;",
+        );
+        let mut indices: Vec<usize> = update_values
+            .split("values[")
+            .skip(1)
+            .map(|v| v[..v.find(']').unwrap()].parse().unwrap())
+            .collect();
+        indices.sort();
+        assert_eq!(
+            indices,
+            (0..FilterType::slider_values_len()).collect::<Vec<usize>>()
+        );
     }
 }
@@ -0,0 +1,422 @@
+use std::fmt::Debug;
+
+use image::{ImageBuffer, Pixel};
+
+pub use LVIElib::image_geometry::Interpolation;
+use LVIElib::image_geometry::{homography, largest_rotated_rect, perspective_matrix, rotate};
+pub use LVIElib::lens_correction::LensModel;
+
+use crate::img_processing::crop;
+
+// smallest side of a crop, in fractions of the image
+const MIN_CROP: f32 = 0.02;
+
+// largest free rotation in degrees, in both directions
+pub const MAX_ANGLE: f32 = 45.0;
+
+// how much a keystone slider at its end moves the corners of an edge, in fractions of the image
+const MAX_KEYSTONE: f32 = 0.25;
+
+// the corners of the whole image, clockwise from the top left one
+const FULL_IMAGE: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+// aspect ratios of the crop tool, as width : height
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum AspectRatio {
+    #[default]
+    Free,
+    Square,
+    ThreeTwo,
+    FourFive,
+    SixteenNine,
+    Custom(f32, f32),
+}
+
+impl AspectRatio {
+    // the order of the presets in the UI
+    pub fn from_index(index: usize, custom: (f32, f32)) -> AspectRatio {
+        match index {
+            1 => AspectRatio::Square,
+            2 => AspectRatio::ThreeTwo,
+            3 => AspectRatio::FourFive,
+            4 => AspectRatio::SixteenNine,
+            5 => AspectRatio::Custom(custom.0.max(1.0), custom.1.max(1.0)),
+            _ => AspectRatio::Free,
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        match self {
+            AspectRatio::Free => 0,
+            AspectRatio::Square => 1,
+            AspectRatio::ThreeTwo => 2,
+            AspectRatio::FourFive => 3,
+            AspectRatio::SixteenNine => 4,
+            AspectRatio::Custom(..) => 5,
+        }
+    }
+
+    // the same ratio for the image turned by 90 degrees
+    pub fn rotated(&self) -> AspectRatio {
+        match *self {
+            AspectRatio::Free | AspectRatio::Square => *self,
+            AspectRatio::Custom(w, h) => AspectRatio::Custom(h, w),
+            _ => {
+                let r = self.ratio().unwrap();
+                AspectRatio::Custom(1.0, r)
+            }
+        }
+    }
+
+    // width / height in pixels, `None` if the ratio is free
+    pub fn ratio(&self) -> Option<f32> {
+        match *self {
+            AspectRatio::Free => None,
+            AspectRatio::Square => Some(1.0),
+            AspectRatio::ThreeTwo => Some(3.0 / 2.0),
+            AspectRatio::FourFive => Some(4.0 / 5.0),
+            AspectRatio::SixteenNine => Some(16.0 / 9.0),
+            AspectRatio::Custom(w, h) => Some(w / h),
+        }
+    }
+}
+
+// crop rectangle in fractions of the image with the origin in the top left corner,
+// it does not depend on the resolution so the preview and the export see the same crop
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crop {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub aspect: AspectRatio,
+}
+
+impl Default for Crop {
+    fn default() -> Self {
+        Crop {
+            x: 0.0,
+            y: 0.0,
+            width: 1.0,
+            height: 1.0,
+            aspect: AspectRatio::Free,
+        }
+    }
+}
+
+impl Crop {
+    pub fn is_full(&self) -> bool {
+        self.x <= 0.0 && self.y <= 0.0 && self.width >= 1.0 && self.height >= 1.0
+    }
+
+    // x, y, width and height in pixels of an image of the given size
+    pub fn to_pixels(&self, width: u32, height: u32) -> (u32, u32, u32, u32) {
+        let x = ((self.x * width as f32).round() as u32).min(width.saturating_sub(1));
+        let y = ((self.y * height as f32).round() as u32).min(height.saturating_sub(1));
+        let w = ((self.width * width as f32).round() as u32).clamp(1, width - x);
+        let h = ((self.height * height as f32).round() as u32).clamp(1, height - y);
+        (x, y, w, h)
+    }
+
+    pub fn apply<P: Pixel>(
+        &self,
+        img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P::Subpixel: Debug,
+    {
+        if self.is_full() || img.width() == 0 || img.height() == 0 {
+            return img.clone();
+        }
+        let (x, y, w, h) = self.to_pixels(img.width(), img.height());
+        crop(img, x, y, w, h)
+    }
+
+    // the ratio between the sides in fractions of an image whose width / height is `image_ratio`
+    fn fraction_ratio(&self, image_ratio: f32) -> Option<f32> {
+        self.aspect.ratio().map(|r| r / image_ratio)
+    }
+
+    // the largest rectangle with the aspect ratio of the crop centered in the current one
+    pub fn constrained(&self, image_ratio: f32) -> Crop {
+        let Some(k) = self.fraction_ratio(image_ratio) else {
+            return *self;
+        };
+
+        let (cx, cy) = (self.x + self.width / 2.0, self.y + self.height / 2.0);
+        let (mut w, mut h) = (self.width, self.height);
+        if w / h > k {
+            w = h * k;
+        } else {
+            h = w / k;
+        }
+
+        Crop {
+            x: (cx - w / 2.0).clamp(0.0, 1.0 - w),
+            y: (cy - h / 2.0).clamp(0.0, 1.0 - h),
+            width: w,
+            height: h,
+            aspect: self.aspect,
+        }
+    }
+
+    // moves a corner (0 top left, 1 top right, 2 bottom right, 3 bottom left)
+    // to `point` keeping the opposite corner fixed and the aspect ratio of the crop
+    pub fn move_corner(&self, corner: usize, point: [f32; 2], image_ratio: f32) -> Crop {
+        let right = corner == 1 || corner == 2;
+        let bottom = corner == 2 || corner == 3;
+
+        // the fixed corner and the room left until the borders of the image
+        let ax = if right { self.x } else { self.x + self.width };
+        let ay = if bottom { self.y } else { self.y + self.height };
+        let max_w = if right { 1.0 - ax } else { ax };
+        let max_h = if bottom { 1.0 - ay } else { ay };
+
+        let mut w = (if right { point[0] - ax } else { ax - point[0] })
+            .max(MIN_CROP)
+            .min(max_w);
+        let mut h = (if bottom { point[1] - ay } else { ay - point[1] })
+            .max(MIN_CROP)
+            .min(max_h);
+
+        if let Some(k) = self.fraction_ratio(image_ratio) {
+            if w / h > k {
+                w = h * k;
+            } else {
+                h = w / k;
+            }
+            if w > max_w {
+                (w, h) = (max_w, max_w / k);
+            }
+            if h > max_h {
+                (w, h) = (max_h * k, max_h);
+            }
+        }
+
+        Crop {
+            x: if right { ax } else { ax - w },
+            y: if bottom { ay } else { ay - h },
+            width: w,
+            height: h,
+            aspect: self.aspect,
+        }
+    }
+
+    // the same crop for the image turned by 90 degrees
+    pub fn rotated_90(&self, clockwise: bool) -> Crop {
+        let (x, y) = if clockwise {
+            (1.0 - self.y - self.height, self.x)
+        } else {
+            (self.y, 1.0 - self.x - self.width)
+        };
+        Crop {
+            x,
+            y,
+            width: self.height,
+            height: self.width,
+            aspect: self.aspect.rotated(),
+        }
+    }
+
+    // moves the top left corner keeping the size, the crop stays inside the image
+    pub fn moved_to(&self, x: f32, y: f32) -> Crop {
+        Crop {
+            x: x.clamp(0.0, 1.0 - self.width),
+            y: y.clamp(0.0, 1.0 - self.height),
+            ..*self
+        }
+    }
+}
+
+// the geometric stages of the rendering: the lens correction, the rotation and the perspective
+// are rendered before the filters, the crop after the local adjustments
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Geometry {
+    pub lens: LensModel,
+    pub rotation: Rotation,
+    pub perspective: Perspective,
+    pub crop: Crop,
+    // false if the image is rendered whole, the crop still frames the vignette
+    pub cropped: bool,
+}
+
+// rotation of the image, applied before the filters so the masks and the crop
+// are placed on the rotated image
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Rotation {
+    // turns by 90 degrees clockwise, from 0 to 3
+    pub quarter_turns: u8,
+    // free rotation in degrees clockwise, from -MAX_ANGLE to MAX_ANGLE
+    pub angle: f32,
+    pub interpolation: Interpolation,
+    // the image is cropped to the largest rectangle without empty corners
+    pub auto_crop: bool,
+}
+
+impl Rotation {
+    pub fn is_identity(&self) -> bool {
+        self.quarter_turns == 0 && self.angle == 0.0
+    }
+
+    pub fn turned(&self, clockwise: bool) -> Rotation {
+        Rotation {
+            quarter_turns: (self.quarter_turns + if clockwise { 1 } else { 3 }) % 4,
+            ..*self
+        }
+    }
+
+    pub fn with_angle(&self, angle: f32) -> Rotation {
+        Rotation {
+            angle: angle.clamp(-MAX_ANGLE, MAX_ANGLE),
+            ..*self
+        }
+    }
+
+    // the angle that levels a line going from `a` to `b`, in pixels of the rotated image.
+    // Lines closer to the vertical are made vertical
+    pub fn straightened(&self, a: [f32; 2], b: [f32; 2]) -> Rotation {
+        if a == b {
+            return *self;
+        }
+        // counterclockwise angle of the line, y points down
+        let tilt = (a[1] - b[1]).atan2(b[0] - a[0]).to_degrees();
+        let tilt = tilt - 90.0 * (tilt / 90.0).round();
+        self.with_angle(self.angle + tilt)
+    }
+
+    // size of the rotated image of an image of the given size
+    pub fn dimensions(&self, width: u32, height: u32) -> (u32, u32) {
+        let (width, height) = if self.quarter_turns % 2 == 1 {
+            (height, width)
+        } else {
+            (width, height)
+        };
+        if !self.auto_crop || self.angle == 0.0 {
+            return (width, height);
+        }
+        let (w, h) = largest_rotated_rect(width as f32, height as f32, self.angle);
+        ((w.floor() as u32).max(1), (h.floor() as u32).max(1))
+    }
+
+    pub fn apply<P>(
+        &self,
+        img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P: Pixel + Send + Sync + 'static,
+        P::Subpixel: Send + Sync,
+    {
+        let turned = match self.quarter_turns {
+            1 => image::imageops::rotate90(img),
+            2 => image::imageops::rotate180(img),
+            3 => image::imageops::rotate270(img),
+            _ => img.clone(),
+        };
+        if self.angle == 0.0 {
+            return turned;
+        }
+        rotate(&turned, -self.angle, self.interpolation, self.auto_crop)
+    }
+}
+
+// perspective correction, the quadrilateral with the corners in `corners` (fractions of the image,
+// clockwise from the top left) moved by the keystone sliders is stretched over the whole image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Perspective {
+    pub corners: [[f32; 2]; 4],
+    // from -1 to 1, positive values stretch the top of the image and negative ones the bottom
+    pub vertical: f32,
+    // from -1 to 1, positive values stretch the left side of the image and negative ones the right
+    pub horizontal: f32,
+}
+
+impl Default for Perspective {
+    fn default() -> Self {
+        Perspective {
+            corners: FULL_IMAGE,
+            vertical: 0.0,
+            horizontal: 0.0,
+        }
+    }
+}
+
+impl Perspective {
+    pub fn is_identity(&self) -> bool {
+        self.quad() == FULL_IMAGE
+    }
+
+    // the corners moved by the keystone sliders
+    pub fn quad(&self) -> [[f32; 2]; 4] {
+        let mut quad = self.corners;
+        let (v, h) = (self.vertical * MAX_KEYSTONE, self.horizontal * MAX_KEYSTONE);
+        // the corners of the stretched edge get closer
+        let (top, bottom) = (v.max(0.0), (-v).max(0.0));
+        let (left, right) = (h.max(0.0), (-h).max(0.0));
+        quad[0][0] += top;
+        quad[1][0] -= top;
+        quad[3][0] += bottom;
+        quad[2][0] -= bottom;
+        quad[0][1] += left;
+        quad[3][1] -= left;
+        quad[1][1] += right;
+        quad[2][1] -= right;
+        quad
+    }
+
+    // moves a corner of the quadrilateral to `point`, the keystone sliders keep their values
+    pub fn with_corner(&self, corner: usize, point: [f32; 2]) -> Perspective {
+        let (i, quad, mut corners) = (corner % 4, self.quad(), self.corners);
+        for k in 0..2 {
+            corners[i][k] += point[k].clamp(0.0, 1.0) - quad[i][k];
+        }
+        Perspective { corners, ..*self }
+    }
+
+    pub fn with_keystone(&self, vertical: f32, horizontal: f32) -> Perspective {
+        Perspective {
+            vertical: vertical.clamp(-1.0, 1.0),
+            horizontal: horizontal.clamp(-1.0, 1.0),
+            ..*self
+        }
+    }
+
+    // the same correction for the image turned by 90 degrees
+    pub fn rotated_90(&self, clockwise: bool) -> Perspective {
+        let c = self.corners;
+        if clockwise {
+            Perspective {
+                corners: [3, 0, 1, 2].map(|i| [1.0 - c[i][1], c[i][0]]),
+                vertical: self.horizontal,
+                horizontal: -self.vertical,
+            }
+        } else {
+            Perspective {
+                corners: [1, 2, 3, 0].map(|i| [c[i][1], 1.0 - c[i][0]]),
+                vertical: -self.horizontal,
+                horizontal: self.vertical,
+            }
+        }
+    }
+
+    pub fn apply<P>(
+        &self,
+        img: &ImageBuffer<P, Vec<P::Subpixel>>,
+    ) -> ImageBuffer<P, Vec<P::Subpixel>>
+    where
+        P: Pixel + Send + Sync,
+        P::Subpixel: Send + Sync,
+    {
+        let (width, height) = (img.width() as f32, img.height() as f32);
+        let to_pixels = |points: [[f32; 2]; 4]| points.map(|p| [p[0] * width, p[1] * height]);
+        if self.is_identity() {
+            return img.clone();
+        }
+
+        // every point of the output comes from the quadrilateral
+        match perspective_matrix(to_pixels(FULL_IMAGE), to_pixels(self.quad())) {
+            Some(matrix) => homography(img, &matrix, Interpolation::Bilinear),
+            // degenerate quadrilateral
+            None => img.clone(),
+        }
+    }
+}
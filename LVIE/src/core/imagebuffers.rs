@@ -4,7 +4,11 @@ use std::{
 };
 
 use image::{Pixel, Primitive};
-use LVIElib::{hsl::HslaImage, oklab::OklabaImage};
+use LVIElib::{
+    hsl::HslaImage,
+    oklab::{Oklaba, OklabaImage},
+    oklch::{Oklcha, OklchaImage},
+};
 use LVIE_GPU::Pod;
 
 use LVIElib::traits::*;
@@ -22,8 +26,9 @@ where
     rgb: CRgbaImage<P>,
     hsl: HslaImage,
     oklab: OklabaImage,
-    enbled: [bool; 3],
-    updated: [bool; 3],
+    oklch: OklchaImage,
+    enbled: [bool; 4],
+    updated: [bool; 4],
 }
 
 #[allow(dead_code)]
@@ -37,8 +42,9 @@ where
             rgb: CRgbaImage::<P>::default(),
             hsl: HslaImage::default(),
             oklab: OklabaImage::default(),
-            enbled: [false; 3],
-            updated: [true; 3],
+            oklch: OklchaImage::default(),
+            enbled: [false; 4],
+            updated: [true; 4],
         }
     }
 
@@ -47,8 +53,9 @@ where
             rgb: img,
             hsl: HslaImage::default(),
             oklab: OklabaImage::default(),
-            enbled: [true, false, false],
-            updated: [true; 3],
+            oklch: OklchaImage::default(),
+            enbled: [true, false, false, false],
+            updated: [true, false, false, false],
         }
     }
 
@@ -57,8 +64,9 @@ where
             rgb: CRgbaImage::<P>::default(),
             hsl: img,
             oklab: OklabaImage::default(),
-            enbled: [false, true, false],
-            updated: [true; 3],
+            oklch: OklchaImage::default(),
+            enbled: [false, true, false, false],
+            updated: [false, true, false, false],
         }
     }
 
@@ -67,17 +75,29 @@ where
             rgb: CRgbaImage::<P>::default(),
             hsl: HslaImage::default(),
             oklab: img,
-            enbled: [false, false, true],
-            updated: [true; 3],
+            oklch: OklchaImage::default(),
+            enbled: [false, false, true, false],
+            updated: [false, false, true, false],
         }
     }
 
-    pub fn set_updates(&mut self, hsl: bool, oklab: bool) {
-        self.enbled = [true, hsl, oklab];
+    pub fn from_oklch(img: OklchaImage) -> ImageBuffers<P> {
+        ImageBuffers {
+            rgb: CRgbaImage::<P>::default(),
+            hsl: HslaImage::default(),
+            oklab: OklabaImage::default(),
+            oklch: img,
+            enbled: [false, false, false, true],
+            updated: [false, false, false, true],
+        }
     }
 
-    pub fn set_updated(&mut self, rgb: bool, hsl: bool, oklab: bool) {
-        self.updated = [rgb, hsl, oklab];
+    pub fn set_updates(&mut self, hsl: bool, oklab: bool, oklch: bool) {
+        self.enbled = [true, hsl, oklab, oklch];
+    }
+
+    pub fn set_updated(&mut self, rgb: bool, hsl: bool, oklab: bool, oklch: bool) {
+        self.updated = [rgb, hsl, oklab, oklch];
     }
 
     pub fn get_rgb(&self) -> &CRgbaImage<P> {
@@ -89,6 +109,9 @@ where
     pub fn get_oklab(&self) -> &OklabaImage {
         &self.oklab
     }
+    pub fn get_oklch(&self) -> &OklchaImage {
+        &self.oklch
+    }
 
     pub fn get_rgb_updated(&mut self) -> &CRgbaImage<P> {
         self.update_rgb();
@@ -102,6 +125,10 @@ where
         self.update_oklab();
         &self.oklab
     }
+    pub fn get_oklch_updated(&mut self) -> &OklchaImage {
+        self.update_oklch();
+        &self.oklch
+    }
 
     pub fn get_rgb_mut_updated(&mut self) -> &mut CRgbaImage<P> {
         self.update_rgb();
@@ -118,25 +145,36 @@ where
         &mut self.oklab
     }
 
+    pub fn get_oklch_mut_updated(&mut self) -> &mut OklchaImage {
+        self.update_oklch();
+        &mut self.oklch
+    }
+
     pub fn replace_rgb(&mut self, new_rgb: CRgbaImage<P>) {
         self.rgb = new_rgb;
-        self.updated = [true, false, false];
+        self.updated = [true, false, false, false];
     }
 
     pub fn replace_hsl(&mut self, new_hsl: HslaImage) {
         self.hsl = new_hsl;
-        self.updated = [false, true, false];
+        self.updated = [false, true, false, false];
     }
 
     pub fn replace_oklab(&mut self, new_oklab: OklabaImage) {
         self.oklab = new_oklab;
-        self.updated = [false, false, true];
+        self.updated = [false, false, true, false];
+    }
+
+    pub fn replace_oklch(&mut self, new_oklch: OklchaImage) {
+        self.oklch = new_oklch;
+        self.updated = [false, false, false, true];
     }
 
     pub fn update(&mut self) {
         self.update_rgb();
         self.update_hsl();
         self.update_oklab();
+        self.update_oklch();
     }
 
     pub fn update_rgb(&mut self) {
@@ -161,6 +199,15 @@ where
                 s.elapsed().as_millis()
             );
             self.updated[0] = true;
+        } else if self.updated[3] {
+            let s = std::time::Instant::now();
+            self.rgb =
+                unsafe { crate::core::processors::convert_oklcha_to_rgba(&self.oklch).unwrap() };
+            println!(
+                "Conversion oklch -> rgb done in {}ms",
+                s.elapsed().as_millis()
+            );
+            self.updated[0] = true;
         }
     }
 
@@ -169,7 +216,7 @@ where
             return;
         }
 
-        if self.updated[2] {
+        if !self.updated[0] {
             self.update_rgb();
         }
 
@@ -206,7 +253,29 @@ where
             return;
         }
 
-        if self.updated[1] {
+        if self.updated[3] {
+            // the polar form is converted directly, without passing through rgb
+            let s = std::time::Instant::now();
+            self.oklab = OklabaImage::from_vec(
+                self.oklch.width(),
+                self.oklch.height(),
+                self.oklch
+                    .par_chunks(4)
+                    .flat_map_iter(|p| {
+                        Oklaba::from(Oklcha::from_components([p[0], p[1], p[2], p[3]])).to_vec()
+                    })
+                    .collect(),
+            )
+            .unwrap();
+            println!(
+                "Conversion oklch -> oklab done in {}ms",
+                s.elapsed().as_millis()
+            );
+            self.updated[2] = true;
+            return;
+        }
+
+        if !self.updated[0] {
             self.update_rgb();
         }
 
@@ -241,10 +310,40 @@ where
         self.updated[2] = true;
     }
 
+    pub fn update_oklch(&mut self) {
+        if self.updated[3] {
+            return;
+        }
+
+        // the polar form is converted directly from oklab when it is available
+        if !self.updated[2] {
+            self.update_oklab();
+        }
+
+        let s = std::time::Instant::now();
+        self.oklch = OklchaImage::from_vec(
+            self.oklab.width(),
+            self.oklab.height(),
+            self.oklab
+                .par_chunks(4)
+                .flat_map_iter(|p| {
+                    Oklcha::from(Oklaba::from_components([p[0], p[1], p[2], p[3]])).to_vec()
+                })
+                .collect(),
+        )
+        .unwrap();
+        println!(
+            "Conversion oklab -> oklch done in {}ms",
+            s.elapsed().as_millis()
+        );
+        self.updated[3] = true;
+    }
+
     pub fn reset(&mut self) {
         self.rgb = CRgbaImage::<P>::default();
         self.hsl = HslaImage::default();
         self.oklab = OklabaImage::default();
-        self.updated = [true; 3];
+        self.oklch = OklchaImage::default();
+        self.updated = [true; 4];
     }
 }
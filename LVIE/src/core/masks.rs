@@ -1,40 +1,791 @@
 use std::usize;
 
-use LVIElib::{traits::Scale, utils::boundary_fill};
+use rayon::prelude::*;
+use LVIElib::{
+    blurs::gaussianblur::gaussian_blur_f32,
+    oklab::OklabaImage,
+    range_mask::{color_range, luminance_range, sample_color},
+    rasterize::{fill_polygon, paint_stroke, Brush},
+};
+
+use super::FilterArray;
 
 #[derive(Debug)]
 pub enum MaskError {
     PointNotFound,
     MaskNotClosed,
+    MaskNotFound,
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// converts a point from the mask coordinates (0..100, y pointing up) to pixels
+fn to_pixels(point: [f32; 2], width: f32, height: f32) -> [f32; 2] {
+    [
+        point[0] * width / 100.0,
+        (100.0 - point[1]) * height / 100.0,
+    ]
+}
+
+// moves a point in the mask coordinates as the image is turned by 90 degrees
+fn rotate_point(point: [f32; 2], clockwise: bool) -> [f32; 2] {
+    if clockwise {
+        [point[1], 100.0 - point[0]]
+    } else {
+        [100.0 - point[1], point[0]]
+    }
+}
+
+// graduated masks, all the coordinates are in percent of the image with y pointing up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GradientMask {
+    // the effect is full before `start` and fades out until `end`
+    Linear {
+        start: [f32; 2],
+        end: [f32; 2],
+    },
+    // ellipse with the radii in percent of width and height,
+    // `feather` is the fraction of the radius where the effect fades out
+    Radial {
+        center: [f32; 2],
+        radius: [f32; 2],
+        feather: f32,
+    },
+}
+
+impl GradientMask {
+    pub fn linear() -> GradientMask {
+        GradientMask::Linear {
+            start: [50.0, 70.0],
+            end: [50.0, 30.0],
+        }
+    }
+
+    pub fn radial() -> GradientMask {
+        GradientMask::Radial {
+            center: [50.0, 50.0],
+            radius: [25.0, 25.0],
+            feather: 0.5,
+        }
+    }
+
+    // weight of the point (in pixels) of an image of the given size
+    fn weight(&self, point: [f32; 2], width: f32, height: f32) -> f32 {
+        match *self {
+            GradientMask::Linear { start, end } => {
+                let s = to_pixels(start, width, height);
+                let e = to_pixels(end, width, height);
+                let (dx, dy) = (e[0] - s[0], e[1] - s[1]);
+                let length = dx * dx + dy * dy;
+                if length == 0.0 {
+                    return 1.0;
+                }
+                let t = ((point[0] - s[0]) * dx + (point[1] - s[1]) * dy) / length;
+                1.0 - smoothstep(0.0, 1.0, t)
+            }
+            GradientMask::Radial {
+                center,
+                radius,
+                feather,
+            } => {
+                let c = to_pixels(center, width, height);
+                let rx = (radius[0] * width / 100.0).max(f32::EPSILON);
+                let ry = (radius[1] * height / 100.0).max(f32::EPSILON);
+                let d =
+                    (((point[0] - c[0]) / rx).powi(2) + ((point[1] - c[1]) / ry).powi(2)).sqrt();
+                1.0 - smoothstep(1.0 - feather.clamp(0.0, 1.0), 1.0, d)
+            }
+        }
+    }
+
+    /// weight of every pixel of an image of the given size, stored row by row, from 0 to 1
+    pub fn weight_map(&self, width: u32, height: u32) -> Vec<f32> {
+        let mut out = vec![0f32; (width * height) as usize];
+        if width == 0 {
+            return out;
+        }
+
+        out.par_chunks_mut(width as usize)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, w) in row.iter_mut().enumerate() {
+                    *w = self.weight(
+                        [x as f32 + 0.5, y as f32 + 0.5],
+                        width as f32,
+                        height as f32,
+                    );
+                }
+            });
+        out
+    }
+
+    // the points that can be dragged on the image space
+    pub fn handles(&self) -> Vec<[f32; 2]> {
+        match *self {
+            GradientMask::Linear { start, end } => vec![start, end],
+            GradientMask::Radial { center, radius, .. } => vec![
+                center,
+                [center[0] + radius[0], center[1]],
+                [center[0], center[1] + radius[1]],
+            ],
+        }
+    }
+
+    // returns the old position of the handle
+    pub fn move_handle(&mut self, index: usize, point: [f32; 2]) -> Result<[f32; 2], MaskError> {
+        let old = *self.handles().get(index).ok_or(MaskError::PointNotFound)?;
+        match self {
+            GradientMask::Linear { start, end } => {
+                if index == 0 {
+                    *start = point;
+                } else {
+                    *end = point;
+                }
+            }
+            GradientMask::Radial { center, radius, .. } => match index {
+                0 => *center = point,
+                // the radius handles stay on the axes of the ellipse
+                1 => radius[0] = (point[0] - center[0]).abs(),
+                _ => radius[1] = (point[1] - center[1]).abs(),
+            },
+        }
+        Ok(old)
+    }
+
+    pub fn feather(&self) -> Option<f32> {
+        match *self {
+            GradientMask::Radial { feather, .. } => Some(feather),
+            _ => None,
+        }
+    }
+
+    pub fn set_feather(&mut self, value: f32) {
+        if let GradientMask::Radial { feather, .. } = self {
+            *feather = value.clamp(0.0, 1.0);
+        }
+    }
+
+    // the same gradient on the image turned by 90 degrees
+    pub fn rotated_90(&self, clockwise: bool) -> GradientMask {
+        match *self {
+            GradientMask::Linear { start, end } => GradientMask::Linear {
+                start: rotate_point(start, clockwise),
+                end: rotate_point(end, clockwise),
+            },
+            GradientMask::Radial {
+                center,
+                radius,
+                feather,
+            } => GradientMask::Radial {
+                center: rotate_point(center, clockwise),
+                radius: [radius[1], radius[0]],
+                feather,
+            },
+        }
+    }
+
+    // outline drawn on the image space, in pixels of the viewport
+    fn guide_line(&self, width: f32, height: f32) -> Vec<[f32; 2]> {
+        let mut line = Vec::new();
+        match *self {
+            GradientMask::Linear { start, end } => {
+                let s = to_pixels(start, width, height);
+                let e = to_pixels(end, width, height);
+                let (dx, dy) = (e[0] - s[0], e[1] - s[1]);
+                let length = (dx * dx + dy * dy).sqrt().max(f32::EPSILON);
+                // direction of the lines, perpendicular to the gradient
+                let (nx, ny) = (-dy / length, dx / length);
+                let half = width.max(height);
+
+                for p in [s, e] {
+                    for k in -200..=200 {
+                        let t = half * k as f32 / 200.0;
+                        let q = [p[0] + nx * t, p[1] + ny * t];
+                        if (0.0..=width).contains(&q[0]) && (0.0..=height).contains(&q[1]) {
+                            line.push(q);
+                        }
+                    }
+                }
+            }
+            GradientMask::Radial {
+                center,
+                radius,
+                feather,
+            } => {
+                let c = to_pixels(center, width, height);
+                let (rx, ry) = (radius[0] * width / 100.0, radius[1] * height / 100.0);
+                for scale in [1.0, 1.0 - feather] {
+                    for k in 0..400 {
+                        let a = std::f32::consts::TAU * k as f32 / 400.0;
+                        line.push([c[0] + scale * rx * a.cos(), c[1] + scale * ry * a.sin()]);
+                    }
+                }
+            }
+        }
+        line
+    }
+}
+
+// masks selecting the pixels by their color, computed from the image being edited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RangeMask {
+    // OkLab lightness from `low` to `high`, the effect fades out within `falloff` outside the range
+    Luminance { low: f32, high: f32, falloff: f32 },
+    // the hue and chroma sampled at `sample`, in percent of the image with y pointing up.
+    // `tolerance` goes from 0 to 1
+    Color { sample: [f32; 2], tolerance: f32 },
+}
+
+// radius of the area averaged by the color sampler, relative to the longest side of the image
+const SAMPLE_RADIUS: f32 = 0.005;
+
+impl RangeMask {
+    pub fn luminance() -> RangeMask {
+        RangeMask::Luminance {
+            low: 0.6,
+            high: 1.0,
+            falloff: 0.1,
+        }
+    }
+
+    pub fn color() -> RangeMask {
+        RangeMask::Color {
+            sample: [50.0, 50.0],
+            tolerance: 0.3,
+        }
+    }
+
+    /// weight of every pixel of the image, stored row by row, from 0 to 1
+    pub fn weight_map(&self, image: &OklabaImage) -> Vec<f32> {
+        match *self {
+            RangeMask::Luminance { low, high, falloff } => {
+                luminance_range(image, low, high, falloff)
+            }
+            RangeMask::Color { sample, tolerance } => {
+                let (width, height) = image.dimensions();
+                let p = to_pixels(sample, width as f32, height as f32);
+                let radius = (SAMPLE_RADIUS * width.max(height) as f32).max(1.0);
+                let reference = sample_color(image, p[0] as u32, p[1] as u32, radius as u32);
+                color_range(image, reference, tolerance)
+            }
+        }
+    }
+
+    // the points that can be dragged on the image space
+    pub fn handles(&self) -> Vec<[f32; 2]> {
+        match *self {
+            RangeMask::Luminance { .. } => Vec::new(),
+            RangeMask::Color { sample, .. } => vec![sample],
+        }
+    }
+
+    // returns the old position of the handle
+    pub fn move_handle(&mut self, index: usize, point: [f32; 2]) -> Result<[f32; 2], MaskError> {
+        match self {
+            RangeMask::Color { sample, .. } if index == 0 => {
+                let old = *sample;
+                *sample = [point[0].clamp(0.0, 100.0), point[1].clamp(0.0, 100.0)];
+                Ok(old)
+            }
+            _ => Err(MaskError::PointNotFound),
+        }
+    }
+
+    // the same range on the image turned by 90 degrees, only the color sample moves
+    pub fn rotated_90(&self, clockwise: bool) -> RangeMask {
+        match *self {
+            RangeMask::Color { sample, tolerance } => RangeMask::Color {
+                sample: rotate_point(sample, clockwise),
+                tolerance,
+            },
+            luminance => luminance,
+        }
+    }
+
+    // the parameters of a luminance range are low, high and falloff, a color range has only the tolerance
+    pub fn set_parameter(&mut self, index: usize, value: f32) {
+        let value = value.clamp(0.0, 1.0);
+        match self {
+            RangeMask::Luminance { low, high, falloff } => match index {
+                0 => *low = value.min(*high),
+                1 => *high = value.max(*low),
+                _ => *falloff = value,
+            },
+            RangeMask::Color { tolerance, .. } => *tolerance = value,
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+// a stroke of a brush mask, the points are in percent of the image with y pointing up
+// and the radius is in percent of the longest side, so it can be replayed at any resolution
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrushStroke {
+    pub points: Vec<[f32; 2]>,
+    pub radius: f32,
+    pub flow: f32,
+    pub hardness: f32,
+    pub erase: bool,
+}
+
+impl BrushStroke {
+    // the same stroke on the image turned by 90 degrees
+    pub fn rotated_90(&self, clockwise: bool) -> BrushStroke {
+        BrushStroke {
+            points: self
+                .points
+                .iter()
+                .map(|p| rotate_point(*p, clockwise))
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    // paints the stroke on the weights of an image of the given size
+    fn paint(&self, weights: &mut [f32], width: u32, height: u32) {
+        let points: Vec<[f32; 2]> = self
+            .points
+            .iter()
+            .map(|p| to_pixels(*p, width as f32, height as f32))
+            .collect();
+        let brush = Brush {
+            radius: self.radius * width.max(height) as f32 / 100.0,
+            flow: self.flow,
+            hardness: self.hardness,
+            erase: self.erase,
+        };
+        paint_stroke(weights, width as usize, height as usize, &points, &brush);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum MaskKind {
+    #[default]
+    Bezier,
+    Gradient(GradientMask),
+    Range(RangeMask),
+    // painted with the strokes of the mask
+    Brush,
+}
+
+impl MaskKind {
+    // the same kind on the image turned by 90 degrees,
+    // the points of paths and the strokes of brushes are stored in the mask
+    pub fn rotated_90(&self, clockwise: bool) -> MaskKind {
+        match self {
+            MaskKind::Gradient(g) => MaskKind::Gradient(g.rotated_90(clockwise)),
+            MaskKind::Range(r) => MaskKind::Range(r.rotated_90(clockwise)),
+            kind => *kind,
+        }
+    }
+}
+
+// boolean combination of the shapes of several masks, the leaves are indices in `Data.masks`
+// kept up to date by `remove_mask` and `insert_mask`
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaskTree {
+    Mask(usize),
+    Invert(Box<MaskTree>),
+    Add(Box<MaskTree>, Box<MaskTree>),
+    Subtract(Box<MaskTree>, Box<MaskTree>),
+    Intersect(Box<MaskTree>, Box<MaskTree>),
+}
+
+impl MaskTree {
+    pub fn combine(self, operation: MaskTreeOperation, other: MaskTree) -> MaskTree {
+        let (a, b) = (Box::new(self), Box::new(other));
+        match operation {
+            MaskTreeOperation::Add => MaskTree::Add(a, b),
+            MaskTreeOperation::Subtract => MaskTree::Subtract(a, b),
+            MaskTreeOperation::Intersect => MaskTree::Intersect(a, b),
+        }
+    }
+
+    // true if the shape of the mask at `index` is a leaf of the tree
+    pub fn uses(&self, index: usize) -> bool {
+        match self {
+            MaskTree::Mask(i) => *i == index,
+            MaskTree::Invert(tree) => tree.uses(index),
+            MaskTree::Add(a, b) | MaskTree::Subtract(a, b) | MaskTree::Intersect(a, b) => {
+                a.uses(index) || b.uses(index)
+            }
+        }
+    }
+
+    // the tree once the mask at `index` is removed and the later masks move back by one,
+    // the removed shape is dropped from the combinations. `None` if nothing is left
+    pub fn without(&self, index: usize) -> Option<MaskTree> {
+        let binary = |a: &MaskTree,
+                      b: &MaskTree,
+                      f: fn(Box<MaskTree>, Box<MaskTree>) -> MaskTree| {
+            match (a.without(index), b.without(index)) {
+                (Some(a), Some(b)) => Some(f(Box::new(a), Box::new(b))),
+                (a, b) => a.or(b),
+            }
+        };
+
+        match self {
+            MaskTree::Mask(i) if *i == index => None,
+            MaskTree::Mask(i) if *i > index => Some(MaskTree::Mask(i - 1)),
+            MaskTree::Mask(i) => Some(MaskTree::Mask(*i)),
+            MaskTree::Invert(tree) => tree.without(index).map(|t| MaskTree::Invert(Box::new(t))),
+            MaskTree::Add(a, b) => binary(a, b, MaskTree::Add),
+            // nothing is left to subtract from without the first shape
+            MaskTree::Subtract(a, b) => match (a.without(index), b.without(index)) {
+                (Some(a), Some(b)) => Some(MaskTree::Subtract(Box::new(a), Box::new(b))),
+                (a, _) => a,
+            },
+            MaskTree::Intersect(a, b) => binary(a, b, MaskTree::Intersect),
+        }
+    }
+
+    // the tree once a mask is inserted at `index` and the later masks move forward by one
+    pub fn with_inserted(&self, index: usize) -> MaskTree {
+        let boxed = |tree: &MaskTree| Box::new(tree.with_inserted(index));
+        match self {
+            MaskTree::Mask(i) if *i >= index => MaskTree::Mask(i + 1),
+            MaskTree::Mask(i) => MaskTree::Mask(*i),
+            MaskTree::Invert(tree) => MaskTree::Invert(boxed(tree)),
+            MaskTree::Add(a, b) => MaskTree::Add(boxed(a), boxed(b)),
+            MaskTree::Subtract(a, b) => MaskTree::Subtract(boxed(a), boxed(b)),
+            MaskTree::Intersect(a, b) => MaskTree::Intersect(boxed(a), boxed(b)),
+        }
+    }
+
+    pub fn invert(self) -> MaskTree {
+        match self {
+            // inverting twice gives back the original tree
+            MaskTree::Invert(tree) => *tree,
+            tree => MaskTree::Invert(Box::new(tree)),
+        }
+    }
+
+    /// weights of the combination for the image being edited, stored row by row.
+    /// The shapes are combined as fuzzy sets: the union is the maximum and the intersection the minimum
+    pub fn weight_map(&self, masks: &[Mask], image: &OklabaImage) -> Result<Vec<f32>, MaskError> {
+        let binary = |a: &MaskTree, b: &MaskTree, f: fn(f32, f32) -> f32| {
+            let mut a = a.weight_map(masks, image)?;
+            let b = b.weight_map(masks, image)?;
+            a.par_iter_mut().zip(b).for_each(|(a, b)| *a = f(*a, b));
+            Ok(a)
+        };
+
+        match self {
+            MaskTree::Mask(index) => masks
+                .get(*index)
+                .ok_or(MaskError::MaskNotFound)?
+                .shape_map(image),
+            MaskTree::Invert(tree) => {
+                let mut out = tree.weight_map(masks, image)?;
+                out.par_iter_mut().for_each(|w| *w = 1.0 - *w);
+                Ok(out)
+            }
+            MaskTree::Add(a, b) => binary(a, b, f32::max),
+            MaskTree::Subtract(a, b) => binary(a, b, |a, b| a.min(1.0 - b)),
+            MaskTree::Intersect(a, b) => binary(a, b, f32::min),
+        }
+    }
+
+    // readable form of the tree, like "(Path 1 - Radial 2)"
+    pub fn describe(&self, masks: &[Mask]) -> String {
+        match self {
+            MaskTree::Mask(index) => match masks.get(*index) {
+                Some(mask) => format!("{} {}", mask.name(), index + 1),
+                None => "?".to_string(),
+            },
+            MaskTree::Invert(tree) => format!("not {}", tree.describe(masks)),
+            MaskTree::Add(a, b) => format!("({} + {})", a.describe(masks), b.describe(masks)),
+            MaskTree::Subtract(a, b) => {
+                format!("({} - {})", a.describe(masks), b.describe(masks))
+            }
+            MaskTree::Intersect(a, b) => {
+                format!("({} & {})", a.describe(masks), b.describe(masks))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MaskTreeOperation {
+    Add,
+    Subtract,
+    Intersect,
+}
+
+#[derive(Debug)]
 pub struct Mask {
+    kind: MaskKind,
+    // adjustments applied only where the mask is
+    filters: FilterArray,
     // represent the points of the mask
     mask_points: Vec<[f32; 2]>,
     // represent the control points of the curves between two main points
     bezier_control_points: Vec<[[f32; 2]; 2]>,
     closed: bool,
+    // softness of the edges of a path, from 0 to 1
+    feather: f32,
+    // opacity of the whole mask, from 0 to 1
+    density: f32,
+    // the strokes of a brush mask, in the order they were painted
+    strokes: Vec<BrushStroke>,
+    // the shapes combined to select the pixels adjusted by this mask,
+    // `None` if the mask uses only its own shape
+    combination: Option<MaskTree>,
     __size_copy: (f32, f32),
 }
 
+impl Default for Mask {
+    fn default() -> Self {
+        Mask::new()
+    }
+}
+
+// blur radius of the edges of a path with feather 1, relative to the longest side of the image
+const MAX_FEATHER: f32 = 0.05;
+
 impl Mask {
     pub fn new() -> Mask {
         Mask {
+            kind: MaskKind::Bezier,
+            filters: FilterArray::new(None),
             mask_points: Vec::new(),
             bezier_control_points: Vec::new(),
             closed: false,
+            feather: 0.0,
+            density: 1.0,
+            strokes: Vec::new(),
+            combination: None,
             __size_copy: (100.0, 100.0),
         }
     }
 
+    pub fn from_gradient(gradient: GradientMask) -> Mask {
+        Mask {
+            kind: MaskKind::Gradient(gradient),
+            ..Mask::new()
+        }
+    }
+
+    pub fn from_range(range: RangeMask) -> Mask {
+        Mask {
+            kind: MaskKind::Range(range),
+            ..Mask::new()
+        }
+    }
+
+    pub fn brush() -> Mask {
+        Mask {
+            kind: MaskKind::Brush,
+            ..Mask::new()
+        }
+    }
+
+    // starts a new stroke, it is ignored if the mask is not a brush
+    pub fn begin_stroke(&mut self, stroke: BrushStroke) {
+        if self.kind == MaskKind::Brush {
+            self.strokes.push(stroke);
+        }
+    }
+
+    // adds a point to the stroke being painted
+    pub fn extend_stroke(&mut self, point: [f32; 2]) {
+        if let Some(stroke) = self.strokes.last_mut() {
+            stroke.points.push(point);
+        }
+    }
+
+    pub fn last_stroke(&self) -> Option<&BrushStroke> {
+        self.strokes.last()
+    }
+
+    pub fn push_stroke(&mut self, stroke: BrushStroke) {
+        self.strokes.push(stroke);
+    }
+
+    pub fn pop_stroke(&mut self) -> Option<BrushStroke> {
+        self.strokes.pop()
+    }
+
+    pub fn get_filters(&self) -> &FilterArray {
+        &self.filters
+    }
+
+    pub fn update_filters(&mut self, filters: FilterArray) {
+        self.filters = filters;
+    }
+
+    // true if the mask changes the image
+    pub fn has_adjustments(&self) -> bool {
+        !(&self.filters).into_iter().all(|f| f.is_default())
+    }
+
+    pub fn get_kind(&self) -> &MaskKind {
+        &self.kind
+    }
+
+    pub fn gradient(&self) -> Option<&GradientMask> {
+        match &self.kind {
+            MaskKind::Gradient(g) => Some(g),
+            _ => None,
+        }
+    }
+
+    pub fn gradient_mut(&mut self) -> Option<&mut GradientMask> {
+        match &mut self.kind {
+            MaskKind::Gradient(g) => Some(g),
+            _ => None,
+        }
+    }
+
+    pub fn range(&self) -> Option<&RangeMask> {
+        match &self.kind {
+            MaskKind::Range(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    pub fn range_mut(&mut self) -> Option<&mut RangeMask> {
+        match &mut self.kind {
+            MaskKind::Range(r) => Some(r),
+            _ => None,
+        }
+    }
+
+    // true if the mask is a path drawn point by point, the other masks only have fixed handles
+    pub fn is_path(&self) -> bool {
+        self.kind == MaskKind::Bezier
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self.kind {
+            MaskKind::Bezier => "Path",
+            MaskKind::Gradient(GradientMask::Linear { .. }) => "Linear",
+            MaskKind::Gradient(GradientMask::Radial { .. }) => "Radial",
+            MaskKind::Range(RangeMask::Luminance { .. }) => "Luminance",
+            MaskKind::Range(RangeMask::Color { .. }) => "Color",
+            MaskKind::Brush => "Brush",
+        }
+    }
+
+    // the feather of paths and radial gradients, linear gradients have none
+    pub fn feather(&self) -> Option<f32> {
+        match &self.kind {
+            MaskKind::Gradient(g) => g.feather(),
+            MaskKind::Bezier => Some(self.feather),
+            MaskKind::Range(_) | MaskKind::Brush => None,
+        }
+    }
+
+    pub fn set_feather(&mut self, value: f32) {
+        match &mut self.kind {
+            MaskKind::Gradient(g) => g.set_feather(value),
+            MaskKind::Bezier => self.feather = value.clamp(0.0, 1.0),
+            MaskKind::Range(_) | MaskKind::Brush => {}
+        }
+    }
+
+    pub fn density(&self) -> f32 {
+        self.density
+    }
+
+    pub fn set_density(&mut self, value: f32) {
+        self.density = value.clamp(0.0, 1.0);
+    }
+
+    // moves the shape of the mask with the image turned by 90 degrees
+    pub fn rotate_90(&mut self, clockwise: bool) {
+        self.kind = self.kind.rotated_90(clockwise);
+        self.mask_points = self
+            .mask_points
+            .iter()
+            .map(|p| rotate_point(*p, clockwise))
+            .collect();
+        // the control points following the line between the main points are not stored
+        for point in self.bezier_control_points.iter_mut().flatten() {
+            if *point != [-1.0, -1.0] {
+                *point = rotate_point(*point, clockwise);
+            }
+        }
+        self.strokes = self
+            .strokes
+            .iter()
+            .map(|s| s.rotated_90(clockwise))
+            .collect();
+    }
+
+    pub fn get_combination(&self) -> Option<&MaskTree> {
+        self.combination.as_ref()
+    }
+
+    pub fn set_combination(&mut self, combination: Option<MaskTree>) {
+        self.combination = combination;
+    }
+
+    // alpha matte of the shape alone, stored row by row, from 0 to 1
+    fn shape_map(&self, image: &OklabaImage) -> Result<Vec<f32>, MaskError> {
+        let (width, height) = image.dimensions();
+        match &self.kind {
+            MaskKind::Gradient(g) => Ok(g.weight_map(width, height)),
+            MaskKind::Range(r) => Ok(r.weight_map(image)),
+            MaskKind::Brush => {
+                let mut out = vec![0f32; (width * height) as usize];
+                for stroke in &self.strokes {
+                    stroke.paint(&mut out, width, height);
+                }
+                Ok(out)
+            }
+            MaskKind::Bezier => {
+                if !self.closed {
+                    return Err(MaskError::MaskNotClosed);
+                }
+                let polygon = self.generate_line(width as f32, height as f32);
+                let matte = fill_polygon(&polygon, width as usize, height as usize);
+
+                if self.feather > 0.0 {
+                    let sigma = self.feather * MAX_FEATHER * width.max(height) as f32;
+                    Ok(gaussian_blur_f32(
+                        &matte,
+                        width as usize,
+                        height as usize,
+                        sigma,
+                    ))
+                } else {
+                    Ok(matte)
+                }
+            }
+        }
+    }
+
+    /// weights of the adjustments of the mask for the image being edited,
+    /// stored row by row, from 0 to 1. `masks` are all the masks of the image,
+    /// the ones referenced by the combination of this mask are read from there
+    pub fn weight_map(&self, masks: &[Mask], image: &OklabaImage) -> Result<Vec<f32>, MaskError> {
+        let mut out = match &self.combination {
+            Some(tree) => tree.weight_map(masks, image)?,
+            None => self.shape_map(image)?,
+        };
+
+        if self.density < 1.0 {
+            out.par_iter_mut().for_each(|w| *w *= self.density);
+        }
+        Ok(out)
+    }
+
     pub fn undo_close(&mut self) {
+        if self.kind != MaskKind::Bezier {
+            return;
+        }
         self.closed = false;
         self.bezier_control_points.pop();
     }
 
     pub fn close(&mut self) {
-        if self.closed {
+        if self.closed || self.kind != MaskKind::Bezier {
             return;
         }
         self.closed = true;
@@ -65,7 +816,13 @@ impl Mask {
 
     pub fn into_rc_model(&self) -> slint::ModelRc<slint::ModelRc<f32>> {
         let mut c: Vec<slint::ModelRc<f32>> = vec![];
-        for i in &self.mask_points {
+        let points = match &self.kind {
+            MaskKind::Gradient(g) => g.handles(),
+            MaskKind::Range(r) => r.handles(),
+            MaskKind::Brush => Vec::new(),
+            MaskKind::Bezier => self.mask_points.clone(),
+        };
+        for i in &points {
             c.push(std::rc::Rc::new(slint::VecModel::from(vec![i[0], i[1]])).into())
         }
         std::rc::Rc::new(slint::VecModel::from(c)).into()
@@ -88,102 +845,29 @@ impl Mask {
 
         let mut line: Vec<slint::ModelRc<f32>> = Vec::new();
 
-        let mp: Vec<[f32; 2]> = (&self.mask_points)
-            .into_iter()
-            .map(|x| {
-                if *x != [-1.0, -1.0] {
-                    [x[0] * width / 100.0, (100.0 - x[1]) * height / 100.0]
-                } else {
-                    *x
-                }
-            })
-            .collect();
-
-        let bcp: Vec<[[f32; 2]; 2]> = (&self.bezier_control_points)
-            .into_iter()
-            .map(|k| {
-                let m: Vec<[f32; 2]> = k
-                    .into_iter()
-                    .map(|x| {
-                        if *x != [-1.0, -1.0] {
-                            [x[0] * width / 100.0, (100.0 - x[1]) * height / 100.0]
-                        } else {
-                            *x
-                        }
-                    })
-                    .collect();
-                [m[0], m[1]]
-            })
-            .collect();
-
-        for i in 0..mp.len() - 1 {
-            let curve = LVIElib::math::bezier_cubic_curve(
-                [
-                    mp[i],
-                    {
-                        let k = bcp[i][0];
-                        if k[0] == -1.0 {
-                            let l = mp[i];
-                            let dx = mp[i + 1][0] - l[0];
-                            let dy = mp[i + 1][1] - l[1];
-                            [l[0] + (dx / 3.0), l[1] + (dy / 3.0)]
-                        } else {
-                            k
-                        }
-                    },
-                    {
-                        let k = bcp[i][1];
-                        if k[0] == -1.0 {
-                            let l = mp[i];
-                            let dx = mp[i + 1][0] - l[0];
-                            let dy = mp[i + 1][1] - l[1];
-                            [l[0] + (2.0 * dx / 3.0), l[1] + (2.0 * dy / 3.0)]
-                        } else {
-                            k
-                        }
-                    },
-                    mp[i + 1],
-                ],
-                None,
-            );
-            for k in curve {
+        if let MaskKind::Gradient(g) = &self.kind {
+            for k in g.guide_line(width, height) {
                 line.push(std::rc::Rc::new(slint::VecModel::from(vec![k[0], k[1]])).into())
             }
+            return std::rc::Rc::new(slint::VecModel::from(line)).into();
         }
 
-        if self.closed {
-            let curve = LVIElib::math::bezier_cubic_curve(
-                [
-                    *mp.last().unwrap(),
-                    {
-                        let k = bcp.last().unwrap()[0];
-                        if k[0] == -1.0 {
-                            let l = mp.last().unwrap();
-                            let dx = mp[0][0] - l[0];
-                            let dy = mp[0][1] - l[1];
-                            [l[0] + (dx / 3.0), l[1] + (dy / 3.0)]
-                        } else {
-                            k
-                        }
-                    },
-                    {
-                        let k = bcp.last().unwrap()[1];
-                        if k[0] == -1.0 {
-                            let l = mp.last().unwrap();
-                            let dx = mp[0][0] - l[0];
-                            let dy = mp[0][1] - l[1];
-                            [l[0] + (2.0 * dx / 3.0), l[1] + (2.0 * dy / 3.0)]
-                        } else {
-                            k
-                        }
-                    },
-                    mp[0],
-                ],
-                None,
-            );
-            for k in curve {
+        // the path followed by the strokes of a brush
+        if self.kind == MaskKind::Brush {
+            for p in self.strokes.iter().flat_map(|s| &s.points) {
+                let k = to_pixels(*p, width, height);
                 line.push(std::rc::Rc::new(slint::VecModel::from(vec![k[0], k[1]])).into())
             }
+            return std::rc::Rc::new(slint::VecModel::from(line)).into();
+        }
+
+        // range masks and empty paths have no outline
+        if self.mask_points.is_empty() {
+            return std::rc::Rc::new(slint::VecModel::from(line)).into();
+        }
+
+        for k in self.generate_line(width, height) {
+            line.push(std::rc::Rc::new(slint::VecModel::from(vec![k[0], k[1]])).into())
         }
 
         std::rc::Rc::new(slint::VecModel::from(line)).into()
@@ -195,6 +879,19 @@ impl Mask {
     ) -> slint::ModelRc<slint::ModelRc<f32>> {
         let mut line: Vec<slint::ModelRc<f32>> = Vec::new();
 
+        // the direction of linear gradients
+        if let MaskKind::Gradient(GradientMask::Linear { start, end }) = self.kind {
+            line.append(&mut generate_linespace(
+                start[0], start[1], end[0], end[1], 100,
+            ));
+            return slint::ModelRc::new(slint::VecModel::from(line));
+        }
+
+        // only paths have control points
+        if !self.is_path() || self.mask_points.is_empty() {
+            return slint::ModelRc::new(slint::VecModel::from(line));
+        }
+
         let p = self.get_control_points();
 
         for i in 0..self.mask_points.len() - 1 {
@@ -235,6 +932,7 @@ impl Mask {
         slint::ModelRc::new(slint::VecModel::from(line))
     }
 
+    // outline of a path, in pixels of an image of the given size
     fn generate_line(&self, width: f32, height: f32) -> Vec<[f32; 2]> {
         let mut line: Vec<[f32; 2]> = Vec::new();
 
@@ -339,95 +1037,16 @@ impl Mask {
         line
     }
 
-    fn generate_track<P>(
-        &self,
-        width: u32,
-        height: u32,
-    ) -> Result<image::ImageBuffer<P, Vec<P::Subpixel>>, MaskError>
-    where
-        P: image::Pixel + std::fmt::Debug + LVIElib::traits::ToHsl + 'static,
-        P::Subpixel:
-            image::Primitive + std::fmt::Debug + Send + Sync + Default + LVIElib::traits::Scale,
-    {
-        if !self.closed {
-            Err(MaskError::MaskNotClosed)
-        } else {
-            let mut points = vec![P::Subpixel::default(); (width * height) as usize];
-
-            for k in self.generate_line(width as f32, height as f32) {
-                let x = k[0].round() as isize;
-                let y = k[1].round() as isize;
-
-                for i in -1..=1 {
-                    for j in -1..=1 {
-                        points[width as usize * (y + j) as usize + (x + i) as usize] =
-                            255u8.scale::<P::Subpixel>();
-                    }
-                }
-            }
-
-            let out = image::ImageBuffer::from_vec(width, height, {
-                let mut out: Vec<P::Subpixel> = Vec::new();
-
-                for k in &points {
-                    let mut channels = vec![*k; P::CHANNEL_COUNT as usize];
-                    out.append(&mut channels);
-                }
-
-                out
-            })
-            .unwrap();
-
-            image::ImageBuffer::<image::Rgb<u8>, Vec<u8>>::from_vec(width, height, {
-                let mut out: Vec<u8> = Vec::new();
-
-                for k in &points {
-                    let channels = [k.scale(); 3];
-                    out.push(channels[0]);
-                    out.push(channels[1]);
-                    out.push(channels[2]);
-                }
-
-                out
-            })
-            .unwrap()
-            .save("track.png")
-            .expect("Failed to save");
-
-            Ok(out)
+    // the points that can be dragged: the main points of a path or the handles of the other masks
+    pub fn get_handles(&self) -> Vec<[f32; 2]> {
+        match &self.kind {
+            MaskKind::Gradient(g) => g.handles(),
+            MaskKind::Range(r) => r.handles(),
+            MaskKind::Brush => Vec::new(),
+            MaskKind::Bezier => self.mask_points.clone(),
         }
     }
 
-    pub fn apply_to_image<P>(
-        &self,
-        image: &image::ImageBuffer<P, Vec<P::Subpixel>>,
-    ) -> Result<image::ImageBuffer<P, Vec<P::Subpixel>>, MaskError>
-    where
-        P: image::Pixel + std::fmt::Debug + LVIElib::traits::ToHsl + 'static + Send + Sync,
-        P::Subpixel: image::Primitive
-            + std::fmt::Debug
-            + Send
-            + Sync
-            + Default
-            + LVIElib::traits::Scale
-            + Send
-            + Sync,
-    {
-        let track: image::ImageBuffer<P, Vec<P::Subpixel>> =
-            self.generate_track(image.width(), image.height())?;
-
-        let out = boundary_fill(
-            &track,
-            None,
-            None,
-            image,
-            P::from_slice(&vec![255u8.scale(); P::CHANNEL_COUNT as usize]),
-            true,
-        );
-
-        Ok(out)
-    }
-
     pub fn get_points(&self) -> &Vec<[f32; 2]> {
         &self.mask_points
     }
@@ -512,6 +1131,16 @@ impl Mask {
     }
 
     pub fn update_point(&mut self, index: usize, point: [f32; 2]) -> Result<(f32, f32), MaskError> {
+        let moved = match &mut self.kind {
+            MaskKind::Gradient(g) => Some(g.move_handle(index, point)?),
+            MaskKind::Range(r) => Some(r.move_handle(index, point)?),
+            MaskKind::Brush => return Err(MaskError::PointNotFound),
+            MaskKind::Bezier => None,
+        };
+        if let Some(old) = moved {
+            return Ok((old[0], old[1]));
+        }
+
         if index >= self.mask_points.len() {
             Err(MaskError::PointNotFound)
         } else {
@@ -555,10 +1184,27 @@ impl Mask {
     }
 
     pub fn is_empty(&self) -> bool {
-        self.mask_points.is_empty()
+        self.kind == MaskKind::Bezier && self.mask_points.is_empty()
     }
 }
 
+// removes the mask at `index`, the combinations of the other masks keep pointing at the same shapes
+pub fn remove_mask(masks: &mut Vec<Mask>, index: usize) -> Mask {
+    let removed = masks.remove(index);
+    for mask in masks.iter_mut() {
+        mask.combination = mask.combination.as_ref().and_then(|t| t.without(index));
+    }
+    removed
+}
+
+// inserts a mask at `index`, the combinations of the other masks keep pointing at the same shapes
+pub fn insert_mask(masks: &mut Vec<Mask>, index: usize, mask: Mask) {
+    for m in masks.iter_mut() {
+        m.combination = m.combination.as_ref().map(|t| t.with_inserted(index));
+    }
+    masks.insert(index, mask);
+}
+
 fn generate_linespace(
     from_x: f32,
     from_y: f32,
@@ -582,3 +1228,172 @@ fn generate_linespace(
 
     out
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: [f32; 2], b: [f32; 2]) -> bool {
+        (a[0] - b[0]).abs() < 1e-4 && (a[1] - b[1]).abs() < 1e-4
+    }
+
+    #[test]
+    fn default_mask_has_full_density() {
+        assert_eq!(Mask::default().density(), 1.0);
+    }
+
+    #[test]
+    fn removing_a_mask_renumbers_the_trees() {
+        let tree = MaskTree::Mask(0)
+            .combine(MaskTreeOperation::Add, MaskTree::Mask(3))
+            .combine(MaskTreeOperation::Subtract, MaskTree::Mask(1).invert());
+
+        assert_eq!(
+            tree.without(2),
+            Some(
+                MaskTree::Mask(0)
+                    .combine(MaskTreeOperation::Add, MaskTree::Mask(2))
+                    .combine(MaskTreeOperation::Subtract, MaskTree::Mask(1).invert())
+            )
+        );
+        // the removed shape is dropped from the combination
+        assert_eq!(
+            tree.without(1),
+            Some(MaskTree::Mask(0).combine(MaskTreeOperation::Add, MaskTree::Mask(2)))
+        );
+        assert_eq!(tree.without(0).and_then(|t| t.without(2)), None);
+        assert_eq!(tree.without(2).unwrap().with_inserted(2), tree);
+    }
+
+    #[test]
+    fn undoing_an_added_mask_keeps_the_combinations() {
+        let mut masks = vec![
+            Mask::from_gradient(GradientMask::radial()),
+            Mask::from_range(RangeMask::luminance()),
+            Mask::brush(),
+        ];
+        // the brush minus the radial gradient
+        let tree = MaskTree::Mask(2).combine(MaskTreeOperation::Subtract, MaskTree::Mask(0));
+        masks[2].set_combination(Some(tree.clone()));
+
+        // undo and redo of the range mask
+        let removed = remove_mask(&mut masks, 1);
+        assert_eq!(
+            masks[1].get_combination(),
+            Some(&MaskTree::Mask(1).combine(MaskTreeOperation::Subtract, MaskTree::Mask(0)))
+        );
+        let image = OklabaImage::new(8, 8);
+        assert!(masks[1].weight_map(&masks, &image).is_ok());
+
+        insert_mask(&mut masks, 1, removed);
+        assert_eq!(masks[2].get_combination(), Some(&tree));
+        assert_eq!(masks[1].name(), "Luminance");
+    }
+
+    #[test]
+    fn quarter_turns_move_the_corners() {
+        // the top left corner goes to the top right with a clockwise turn
+        assert_eq!(rotate_point([0.0, 100.0], true), [100.0, 100.0]);
+        assert_eq!(rotate_point([0.0, 100.0], false), [0.0, 0.0]);
+        assert_eq!(
+            rotate_point(rotate_point([20.0, 70.0], true), false),
+            [20.0, 70.0]
+        );
+    }
+
+    #[test]
+    fn rotated_gradient_follows_the_image() {
+        let radial = GradientMask::Radial {
+            center: [30.0, 80.0],
+            radius: [10.0, 20.0],
+            feather: 0.5,
+        };
+        let (width, height) = (40, 24);
+        let before = radial.weight_map(width, height);
+        // the image turned clockwise is `height` pixels wide
+        let after = radial.rotated_90(true).weight_map(height, width);
+
+        for y in 0..height {
+            for x in 0..width {
+                // the pixel (x, y) moves to (height - 1 - y, x)
+                let turned = after[(x * height + height - 1 - y) as usize];
+                assert!((before[(y * width + x) as usize] - turned).abs() < 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn four_turns_give_back_the_masks() {
+        let mut path = Mask::new();
+        for p in [[10.0, 10.0], [80.0, 20.0], [50.0, 90.0]] {
+            path.add_point(p);
+        }
+        path.close();
+        path.update_control_point([0, 1], [60.0, 5.0]).unwrap();
+
+        let mut brush = Mask::brush();
+        brush.begin_stroke(BrushStroke {
+            points: vec![[5.0, 95.0], [40.0, 60.0]],
+            radius: 2.0,
+            flow: 1.0,
+            hardness: 0.5,
+            erase: false,
+        });
+
+        let mut masks = vec![
+            path,
+            brush,
+            Mask::from_gradient(GradientMask::linear()),
+            Mask::from_range(RangeMask::Color {
+                sample: [25.0, 75.0],
+                tolerance: 0.3,
+            }),
+        ];
+        let before: Vec<(
+            MaskKind,
+            Vec<[f32; 2]>,
+            Vec<[[f32; 2]; 2]>,
+            Vec<BrushStroke>,
+        )> = masks
+            .iter()
+            .map(|m| {
+                (
+                    m.kind,
+                    m.mask_points.clone(),
+                    m.bezier_control_points.clone(),
+                    m.strokes.clone(),
+                )
+            })
+            .collect();
+
+        for mask in &mut masks {
+            mask.rotate_90(true);
+        }
+        assert_eq!(masks[0].get_points()[0], [10.0, 90.0]);
+        assert_eq!(masks[1].last_stroke().unwrap().points[0], [95.0, 95.0]);
+        assert_eq!(masks[3].get_handles(), vec![[75.0, 75.0]]);
+        // the control points that are not stored keep following the main points
+        assert_eq!(masks[0].bezier_control_points[0][0], [-1.0, -1.0]);
+
+        for mask in &mut masks {
+            for _ in 0..3 {
+                mask.rotate_90(true);
+            }
+        }
+        for (mask, (kind, points, control_points, strokes)) in masks.iter().zip(before) {
+            assert_eq!(mask.kind, kind);
+            assert!(mask
+                .mask_points
+                .iter()
+                .zip(&points)
+                .all(|(a, b)| close(*a, *b)));
+            assert_eq!(mask.bezier_control_points, control_points);
+            assert_eq!(mask.strokes, strokes);
+        }
+
+        // undoing the turn gives back the masks
+        masks[0].rotate_90(true);
+        masks[0].rotate_90(false);
+        assert_eq!(masks[0].get_points()[1], [80.0, 20.0]);
+    }
+}
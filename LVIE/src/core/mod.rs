@@ -1,6 +1,7 @@
 pub mod callbacks;
 mod data;
 mod filters;
+mod geometry;
 mod imagebuffers;
 mod masks;
 mod processors;
@@ -8,6 +9,7 @@ mod rendering;
 
 pub use data::Data;
 pub use filters::*;
+pub use geometry::*;
 pub use imagebuffers::ImageBuffers;
 pub use masks::*;
 pub use rendering::*;
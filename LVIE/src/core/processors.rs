@@ -1,14 +1,24 @@
 #![allow(dead_code)]
-use image::{Pixel, Primitive, Rgba, RgbaImage};
+use image::{Pixel, Primitive};
 use rayon::prelude::*;
 use std::sync::{Arc, Mutex};
+use LVIElib::channel_mixer::{black_and_white as black_and_white_pixel, mix_channels};
+use LVIElib::color_grading::grade;
+use LVIElib::contrast::{apply_contrast, ContrastAlgorithm};
+use LVIElib::dehaze::dehaze as dehaze_pixels;
+use LVIElib::grain::add_grain;
+use LVIElib::highlights_shadows::{highlights_shadows, whites_blacks};
 use LVIElib::hsl::HslaImage;
+use LVIElib::hsl_mixer::mix_hsl;
 use LVIElib::linear_srgb::LinSrgba;
+use LVIElib::local_contrast::clarity_texture;
 use LVIElib::matrix::{convolution::laplacian_of_gaussian, Matrix};
 use LVIElib::traits::{cast_color_to_rgba, AsFloat, ToOklab};
+use LVIElib::wavelet_denoise::denoise_channel;
 use LVIElib::white_balance::{xyz_wb_matrix, LINSRGB_TO_XYZ, XYZ_TO_LINSRGB};
 
 use LVIElib::oklab::{Oklaba, OklabaImage};
+use LVIElib::oklch::OklchaImage;
 
 use LVIElib::matrix::convolution::convolve;
 
@@ -90,14 +100,96 @@ where
     )
 }
 
-pub fn saturate(img: &mut HslaImage, value: f32) {
+/// converts an OkLch image back to RGBA, `None` if `P` is not an RGBA pixel
+///
+/// # Safety
+///
+/// the pixels are rebuilt from a flat buffer of subpixels, so `P` must store exactly
+/// red, green, blue and alpha in this order. Only the name of the color model is checked
+pub unsafe fn convert_oklcha_to_rgba<P>(img: &OklchaImage) -> Option<CRgbaImage<P>>
+where
+    P: Pixel + Send + Sync + 'static + Debug + ToOklab,
+    P::Subpixel: Scale + Send + Sync + Primitive + std::fmt::Debug + Pod + Debug,
+{
+    // if target is not rgb we cannot cast it
+    if P::COLOR_MODEL != "RGBA" {
+        return None;
+    }
+
+    let out = Arc::new(Mutex::new(vec![P::Subpixel::DEFAULT_MIN_VALUE; img.len()]));
+
+    img.enumerate_rows().par_bridge().for_each(|r| {
+        let mut row: Vec<P::Subpixel> = Vec::new();
+        for (_, _, p) in r.1 {
+            let rgb = p.to_rgba();
+            let cmp = rgb.channels();
+            row.append(&mut vec![
+                cmp[0].scale(),
+                cmp[1].scale(),
+                cmp[2].scale(),
+                cmp[3].scale(),
+            ]);
+        }
+        out.lock().unwrap()
+            [(r.0 * img.width() * 4) as usize..(r.0 * img.width() * 4 + img.width() * 4) as usize]
+            .clone_from_slice(&row);
+    });
+
+    CRgbaImage::<P>::from_vec(
+        img.width(),
+        img.height(),
+        Arc::try_unwrap(out).unwrap().into_inner().unwrap(),
+    )
+}
+
+// scales the chroma, -1 turns the image to grayscale keeping its perceived lightness
+pub fn saturate(img: &mut OklchaImage, value: f32) {
+    let gain = (1.0 + value).max(0.0);
     img.enumerate_pixels_mut()
         .par_bridge()
         .for_each(|(_, _, pixel)| {
-            *pixel.saturation_mut() += value / 2.0;
+            *pixel.c_mut() *= gain;
         });
 }
 
+pub fn hsl_mixer(img: &mut OklchaImage, parameters: &[f32]) {
+    img.enumerate_pixels_mut()
+        .par_bridge()
+        .for_each(|(_, _, pixel)| mix_hsl(pixel, parameters));
+}
+
+// mixes the channels in linear light, the alpha channel is left unchanged
+pub fn channel_mixer<P>(img: &mut CRgbaImage<P>, parameters: &[f32])
+where
+    P: Pixel + Send + Sync + 'static + Debug,
+    P::Subpixel: Scale + Primitive + Debug + Pod + Send + Sync + AsFloat,
+{
+    let channels = P::CHANNEL_COUNT as usize;
+    if channels < 3 {
+        return;
+    }
+
+    img.par_chunks_mut(channels).for_each(|pixel| {
+        let lin = std::array::from_fn(|c| pixel[c].as_float().clamp(0.0, 1.0).powf(2.2));
+        for (channel, value) in pixel.iter_mut().zip(mix_channels(lin, parameters)) {
+            *channel = value.clamp(0.0, 1.0).powf(1.0 / 2.2).scale();
+        }
+    });
+}
+
+// `parameters` are the enabled flag and the luminance of every hue band
+pub fn black_and_white(img: &mut OklchaImage, parameters: &[f32]) {
+    img.enumerate_pixels_mut()
+        .par_bridge()
+        .for_each(|(_, _, pixel)| black_and_white_pixel(pixel, &parameters[1..]));
+}
+
+pub fn color_grading(img: &mut OklabaImage, parameters: &[f32]) {
+    img.enumerate_pixels_mut()
+        .par_bridge()
+        .for_each(|(_, _, pixel)| grade(pixel, parameters));
+}
+
 pub fn sharpen(img: &mut OklabaImage, value: f32, size: usize) {
     let (mut vl, mut va, mut vb, mut valpha) = (
         Vec::<f32>::new(),
@@ -133,6 +225,88 @@ pub fn sharpen(img: &mut OklabaImage, value: f32, size: usize) {
         });
 }
 
+pub fn contrast(img: &mut OklabaImage, value: f32, algorithm: ContrastAlgorithm) {
+    let mut luma: Vec<f32> = img.pixels().map(|p| *p.l()).collect();
+
+    apply_contrast(&mut luma, value, algorithm);
+
+    img.pixels_mut()
+        .zip(luma)
+        .for_each(|(pixel, l)| *pixel.l_mut() = l);
+}
+
+pub fn shadows_and_highlights(img: &mut OklabaImage, highlights: f32, shadows: f32) {
+    let (width, height) = img.dimensions();
+    let mut luma: Vec<f32> = img.pixels().map(|p| *p.l()).collect();
+
+    highlights_shadows(
+        &mut luma,
+        width as usize,
+        height as usize,
+        highlights,
+        shadows,
+    );
+
+    img.pixels_mut()
+        .zip(luma)
+        .for_each(|(pixel, l)| *pixel.l_mut() = l);
+}
+
+pub fn local_contrast(img: &mut OklabaImage, clarity: f32, texture: f32) {
+    let (width, height) = img.dimensions();
+    let mut luma: Vec<f32> = img.pixels().map(|p| *p.l()).collect();
+
+    clarity_texture(&mut luma, width as usize, height as usize, clarity, texture);
+
+    img.pixels_mut()
+        .zip(luma)
+        .for_each(|(pixel, l)| *pixel.l_mut() = l);
+}
+
+// parameters: amount, size, roughness and seed, the grain is added to the lightness
+pub fn grain(img: &mut OklabaImage, parameters: &[f32]) {
+    let (width, height) = img.dimensions();
+    let mut luma: Vec<f32> = img.pixels().map(|p| *p.l()).collect();
+
+    add_grain(
+        &mut luma,
+        width as usize,
+        height as usize,
+        parameters[0],
+        parameters[1],
+        parameters[2],
+        parameters[3] as u32,
+    );
+
+    img.pixels_mut()
+        .zip(luma)
+        .for_each(|(pixel, l)| *pixel.l_mut() = l);
+}
+
+// parameters: luma strength, chroma strength, threshold and the strength of every scale
+pub fn wavelet_denoise(img: &mut OklabaImage, parameters: &[f32]) {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let (threshold, scales) = (parameters[2], &parameters[3..]);
+
+    for (channel, strength) in [(0, parameters[0]), (1, parameters[1]), (2, parameters[1])] {
+        if strength <= 0.0 {
+            continue;
+        }
+
+        let mut data: Vec<f32> = img.pixels().map(|p| p.channels()[channel]).collect();
+        denoise_channel(&mut data, width, height, strength, threshold, scales);
+        img.pixels_mut()
+            .zip(data)
+            .for_each(|(pixel, v)| pixel.channels_mut()[channel] = v);
+    }
+}
+
+pub fn whites_and_blacks(img: &mut OklabaImage, whites: f32, blacks: f32) {
+    img.par_chunks_mut(4).for_each(|pixel| {
+        pixel[0] = whites_blacks(pixel[0], whites, blacks);
+    });
+}
+
 pub fn exposition(img: &mut HslaImage, value: f32) {
     img.enumerate_pixels_mut()
         .par_bridge()
@@ -182,19 +356,157 @@ pub fn whitebalance<P>(
         });
 }
 
-pub fn apply_curve(img: &RgbaImage, curve: crate::core::Curve) -> RgbaImage {
-    let mut nb = RgbaImage::new(img.width(), img.height());
+// the colors of the image in linear light, the alpha channel is dropped
+pub fn linear_colors<P>(img: &CRgbaImage<P>) -> Vec<[f32; 3]>
+where
+    P: Pixel + Send + Sync + 'static + Debug,
+    P::Subpixel: Scale + Primitive + Debug + Pod + Send + Sync + AsFloat,
+{
+    img.pixels()
+        .map(|p| std::array::from_fn(|c| p.channels()[c].as_float().powf(2.2)))
+        .collect()
+}
+
+// removes the haze with a positive strength or adds it with a negative one
+pub fn dehaze<P>(img: &mut CRgbaImage<P>, strength: f32)
+where
+    P: Pixel + Send + Sync + 'static + Debug,
+    P::Subpixel: Scale + Primitive + Debug + Pod + Send + Sync + AsFloat,
+{
+    let (width, height) = img.dimensions();
+    let mut pixels: Vec<[f32; 3]> = img
+        .pixels()
+        .map(|p| std::array::from_fn(|c| p.channels()[c].as_float()))
+        .collect();
+
+    dehaze_pixels(&mut pixels, width as usize, height as usize, strength);
+
+    img.pixels_mut().zip(pixels).for_each(|(pixel, rgb)| {
+        for (channel, value) in pixel.channels_mut().iter_mut().zip(rgb) {
+            *channel = value.clamp(0.0, 1.0).scale();
+        }
+    });
+}
+
+// `luts` are the baked curves indexed by `CurveChannel`, the red, green and blue curves are
+// applied to their channel, then the luma curve scales the pixel preserving its hue
+// mixes `adjusted` into `img` following the weight of every pixel, from 0 to 1
+pub fn blend_with_weights<P>(img: &mut CRgbaImage<P>, adjusted: &CRgbaImage<P>, weights: &[f32])
+where
+    P: Pixel + Send + Sync + 'static + Debug,
+    P::Subpixel: Scale + Primitive + Debug + Pod + Send + Sync + AsFloat,
+{
+    let channels = P::CHANNEL_COUNT as usize;
+    img.par_chunks_mut(channels)
+        .zip(adjusted.par_chunks(channels))
+        .zip(weights.par_iter())
+        .for_each(|((pixel, other), w)| {
+            if *w <= 0.0 {
+                return;
+            }
+            for (channel, value) in pixel.iter_mut().zip(other) {
+                let (a, b) = (channel.as_float(), value.as_float());
+                *channel = (a + (b - a) * w).scale();
+            }
+        });
+}
+
+pub fn apply_curves<P>(img: &mut CRgbaImage<P>, luts: &[Vec<f32>; 4])
+where
+    P: Pixel + Send + Sync + 'static + Debug,
+    P::Subpixel: Scale + Primitive + Debug + Pod + Send + Sync + AsFloat,
+{
+    img.par_chunks_mut(P::CHANNEL_COUNT as usize)
+        .for_each(|pixel| {
+            let mut rgb = [0f32; 3];
+            for (c, value) in rgb.iter_mut().enumerate() {
+                *value = lut_lookup(&luts[c + 1], pixel[c].as_float());
+            }
+
+            let luma = 0.2126 * rgb[0] + 0.7152 * rgb[1] + 0.0722 * rgb[2];
+            if luma > 0.0 {
+                let gain = lut_lookup(&luts[0], luma) / luma;
+                rgb.iter_mut().for_each(|v| *v *= gain);
+            }
+
+            for (channel, value) in pixel.iter_mut().zip(rgb) {
+                *channel = value.scale();
+            }
+        });
+}
+
+// darkening (negative amount) or brightening (positive amount) of the linear light `lin`,
+// `t` is the weight of the vignette in the pixel
+fn vignette_light(lin: f32, amount: f32, t: f32) -> f32 {
+    if amount < 0.0 {
+        lin * (1.0 + amount * t)
+    } else {
+        lin + (1.0 - lin) * amount * t
+    }
+}
+
+// weight of the vignette in a point of the frame, `u` and `v` are the distances from the center
+// in half sizes of the frame. The roundness goes from a rectangle (-1) to a circle (1)
+fn vignette_weight(u: f32, v: f32, midpoint: f32, roundness: f32, feather: f32) -> f32 {
+    let exponent = 2.0 + 6.0 * (-roundness).max(0.0);
+    let d = (u.abs().powf(exponent) + v.abs().powf(exponent)).powf(1.0 / exponent);
+    let t = ((d - midpoint) / (feather + 1e-3)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+// `parameters` are amount, midpoint, roundness and feather, `frame` is the x, y, width and height
+// in pixels of the rectangle the vignette is centered in, usually the cropped image
+pub fn vignette<P>(img: &mut CRgbaImage<P>, parameters: &[f32], frame: (u32, u32, u32, u32))
+where
+    P: Pixel + Send + Sync + 'static + Debug,
+    P::Subpixel: Scale + Primitive + Debug + Pod + Send + Sync + AsFloat,
+{
+    let (amount, midpoint, roundness, feather) =
+        (parameters[0], parameters[1], parameters[2], parameters[3]);
+    let width = img.width() as usize;
+    if amount == 0.0 || width == 0 {
+        return;
+    }
+
+    let channels = P::CHANNEL_COUNT as usize;
+    let (hw, hh) = (frame.2 as f32 / 2.0, frame.3 as f32 / 2.0);
+    let (cx, cy) = (frame.0 as f32 + hw, frame.1 as f32 + hh);
+    // a round vignette has the same radius on both the axes
+    let radius = (hw * hh).sqrt();
+    let round = roundness.max(0.0);
+    let (sx, sy) = (hw + (radius - hw) * round, hh + (radius - hh) * round);
+
+    img.par_chunks_mut(width * channels)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let v = (y as f32 + 0.5 - cy) / sy;
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                let u = (x as f32 + 0.5 - cx) / sx;
+                let t = vignette_weight(u, v, midpoint, roundness, feather);
+                if t <= 0.0 {
+                    continue;
+                }
+                // the alpha channel is left unchanged
+                for channel in pixel[..channels.min(3)].iter_mut() {
+                    let lin = channel.as_float().clamp(0.0, 1.0).powf(2.2);
+                    *channel = vignette_light(lin, amount, t).powf(1.0 / 2.2).scale();
+                }
+            }
+        });
+}
 
-    for (x, y, pixel) in img.enumerate_pixels() {
-        let cmp = pixel.channels();
-        let np: Rgba<u8> = Rgba([
-            (curve.apply_curve(cmp[0] as f32 / 255.0) * 255.0) as u8,
-            (curve.apply_curve(cmp[1] as f32 / 255.0) * 255.0) as u8,
-            (curve.apply_curve(cmp[2] as f32 / 255.0) * 255.0) as u8,
-            cmp[3],
-        ]);
-        nb.put_pixel(x, y, np);
+// linear interpolation between the samples of the lut, values outside of 0..1
+// (only possible with float images) are shifted by the ends of the curve
+fn lut_lookup(lut: &[f32], value: f32) -> f32 {
+    let last = lut.len() - 1;
+    if value <= 0.0 {
+        return lut[0] + value;
+    } else if value >= 1.0 {
+        return lut[last] + value - 1.0;
     }
 
-    nb
+    let position = value * last as f32;
+    let i = position as usize;
+    let t = position - i as f32;
+    lut[i] + (lut[(i + 1).min(last)] - lut[i]) * t
 }
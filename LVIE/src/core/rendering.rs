@@ -2,16 +2,24 @@ use std::fmt::Debug;
 
 use image::{Pixel, Primitive};
 use LVIElib::blurs::{boxblur::FastBoxBlur, gaussianblur::FastGaussianBlur};
+use LVIElib::contrast::ContrastAlgorithm;
+use LVIElib::lens_correction::correct_lens;
 use LVIE_GPU::{GPUShaderType, Pod, GPU};
 
 use serde::{Deserialize, Serialize};
 
 use LVIElib::traits::*;
 
-use super::processors::{exposition, saturate, sharpen, whitebalance};
+use super::processors::{
+    apply_curves, black_and_white, blend_with_weights, channel_mixer, color_grading, contrast,
+    dehaze, exposition, grain, hsl_mixer, local_contrast, saturate, shadows_and_highlights,
+    sharpen, vignette, wavelet_denoise, whitebalance, whites_and_blacks,
+};
 pub use LVIE_GPU::CRgbaImage;
 
 use super::filters::*;
+use super::geometry::{Geometry, Interpolation, LensModel, Perspective, Rotation};
+use super::masks::Mask;
 use super::ImageBuffers;
 
 #[allow(dead_code)]
@@ -38,6 +46,29 @@ impl RenderingBackends {
     }
 }
 
+// a step of the rendering pipeline, used as key of the rendering cache
+#[derive(Debug, Clone, PartialEq)]
+enum Stage {
+    Lens(LensModel),
+    Rotation(Rotation),
+    Perspective(Perspective),
+    Filter(Filter),
+    Curves(ToneCurves),
+}
+
+impl Stage {
+    // true if the stage would leave the image unchanged
+    fn is_noop(&self) -> bool {
+        match self {
+            Stage::Lens(lens) => lens.is_identity(),
+            Stage::Rotation(rotation) => rotation.is_identity(),
+            Stage::Perspective(perspective) => perspective.is_identity(),
+            Stage::Filter(filter) => filter.is_default(),
+            Stage::Curves(curves) => curves.is_identity(),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Rendering<P>
 where
@@ -47,6 +78,8 @@ where
     backend: RenderingBackends,
     gpu: Option<GPU>,
     pub imagebuffers: ImageBuffers<P>,
+    // output of every stage of the last render, `None` if the stage did not change the image
+    cache: Vec<(Stage, Option<CRgbaImage<P>>)>,
 }
 
 impl<P> Rendering<P>
@@ -61,6 +94,7 @@ where
                     backend,
                     gpu: None,
                     imagebuffers: ImageBuffers::new(),
+                    cache: Vec::new(),
                 }
             }
             RenderingBackends::GPU => {}
@@ -73,88 +107,330 @@ where
             backend,
             gpu: Some(gpu),
             imagebuffers: ImageBuffers::new(),
+            cache: Vec::new(),
         }
     }
 
     pub fn render_data(
         &mut self,
         img: &CRgbaImage<P>,
+        geometry: &Geometry,
         filters: &FilterArray,
+        curves: &ToneCurves,
+        masks: &[Mask],
     ) -> Result<CRgbaImage<P>, crate::core::RenderingError> {
-        let mut out = img.clone();
-
-        for filter in filters {
-            if filter.parameters[0] != filter.filtertype.default()[0] {
-                //println!("applying {:?} with values: {:?}", filter.filtertype, filter.parameters);
-                let gpu_filter: Option<GPUShaderType> = {
-                    match filter.filtertype {
-                        FilterType::Saturation => Some(LVIE_GPU::GPUShaderType::Saturation),
-                        FilterType::Exposition => Some(LVIE_GPU::GPUShaderType::Exposition),
-                        FilterType::WhiteBalance => Some(LVIE_GPU::GPUShaderType::WhiteBalance),
-                        _ => None,
-                    }
-                };
-
-                if self.backend == RenderingBackends::GPU && gpu_filter.is_some() {
-                    let gpu = self.gpu.as_mut().unwrap();
-                    gpu.create_rgb_texture(&out)
-                        .expect("Failed to create a texture!");
-                    let res = gpu.render(&gpu_filter.unwrap(), &filter.parameters);
-                    if res.is_err() {
-                        return Err(RenderingError::GPUERROR(res.unwrap_err()));
-                    } else {
-                        out = res.unwrap();
-                    }
-                } else {
-                    match filter.filtertype {
-                        FilterType::Saturation => {
-                            saturate(
-                                self.imagebuffers.get_hsl_mut_updated(),
-                                filter.parameters[0],
-                            );
-                            self.imagebuffers.set_updated(false, true, false);
-                            out = self.imagebuffers.get_rgb_updated().clone();
-                        }
-                        FilterType::Exposition => {
-                            exposition(
-                                self.imagebuffers.get_hsl_mut_updated(),
-                                filter.parameters[0],
-                            );
-                            self.imagebuffers.set_updated(false, true, false);
-                            out = self.imagebuffers.get_rgb_updated().clone();
-                        }
-                        FilterType::Boxblur => {
-                            out = FastBoxBlur(&out, filter.parameters[0] as u32);
-                        }
-                        FilterType::Sharpening => {
-                            sharpen(
-                                self.imagebuffers.get_oklab_mut_updated(),
-                                filter.parameters[0],
-                                filter.parameters[1] as usize,
-                            );
-                            self.imagebuffers.set_updated(false, false, true);
-                            out = self.imagebuffers.get_rgb_updated().clone();
-                        }
-                        FilterType::GaussianBlur => {
-                            out = FastGaussianBlur(
-                                &out,
-                                filter.parameters[0],
-                                filter.parameters[1] as u8,
-                            )
-                        }
-                        FilterType::WhiteBalance => {
-                            whitebalance(
-                                self.imagebuffers.get_rgb_mut_updated(),
-                                filter.parameters[0],
-                                filter.parameters[1],
-                                filter.parameters[2],
-                                filter.parameters[3],
-                            );
-                            self.imagebuffers.set_updated(true, false, false);
-                            out = self.imagebuffers.get_rgb_mut_updated().clone();
-                        }
-                        _ => unimplemented!(),
-                    }
+        let stages: Vec<Stage> = [
+            Stage::Lens(geometry.lens),
+            Stage::Rotation(geometry.rotation),
+            Stage::Perspective(geometry.perspective),
+        ]
+        .into_iter()
+        .chain(
+            filters
+                .into_iter()
+                .filter(|filter| filter.filtertype != FilterType::Vignette)
+                .map(|filter| Stage::Filter(filter.clone())),
+        )
+        .chain(std::iter::once(Stage::Curves(curves.clone())))
+        .collect();
+
+        // the stages that did not change since the last render are taken from the cache,
+        // the pipeline restarts from the first stage whose parameters differ
+        let start = self
+            .cache
+            .iter()
+            .zip(&stages)
+            .take_while(|((cached, _), stage)| cached == *stage)
+            .count();
+        self.cache.truncate(start);
+
+        let mut out = self.cached_output(start).unwrap_or_else(|| img.clone());
+        self.imagebuffers.replace_rgb(out.clone());
+
+        for stage in stages.into_iter().skip(start) {
+            if stage.is_noop() {
+                // there is no need to store another copy of the image
+                self.cache.push((stage, None));
+                continue;
+            }
+
+            out = match &stage {
+                Stage::Lens(lens) => {
+                    let corrected = correct_lens(&out, lens, Interpolation::Bilinear);
+                    self.imagebuffers.replace_rgb(corrected.clone());
+                    corrected
+                }
+                Stage::Rotation(rotation) => {
+                    let rotated = rotation.apply(&out);
+                    self.imagebuffers.replace_rgb(rotated.clone());
+                    rotated
+                }
+                Stage::Perspective(perspective) => {
+                    let corrected = perspective.apply(&out);
+                    self.imagebuffers.replace_rgb(corrected.clone());
+                    corrected
+                }
+                Stage::Filter(filter) => self.render_stage(&out, filter)?,
+                Stage::Curves(curves) => {
+                    apply_curves(&mut out, &curves.bake_luts());
+                    self.imagebuffers.replace_rgb(out.clone());
+                    out
+                }
+            };
+            self.cache.push((stage, Some(out.clone())));
+        }
+
+        // local adjustments, the filters of every mask are rendered on top of the
+        // previous result and blended following the weights of the mask
+        for mask in masks.iter().filter(|m| m.has_adjustments()) {
+            // range masks read the colors of the image as it is at this point
+            let weights = match mask.weight_map(masks, self.imagebuffers.get_oklab_updated()) {
+                Ok(weights) => weights,
+                // open paths do not select anything
+                Err(_) => continue,
+            };
+
+            let mut local = out.clone();
+            self.imagebuffers.replace_rgb(local.clone());
+            for filter in mask.get_filters() {
+                if !filter.is_default() {
+                    local = self.render_stage(&local, filter)?;
+                }
+            }
+
+            blend_with_weights(&mut out, &local, &weights);
+            self.imagebuffers.replace_rgb(out.clone());
+        }
+
+        // the crop is applied after the masks, so the filters and the masks always see the whole image
+        let frame = if geometry.cropped {
+            out = geometry.crop.apply(&out);
+            (0, 0, out.width(), out.height())
+        } else {
+            geometry.crop.to_pixels(out.width(), out.height())
+        };
+
+        // the vignette follows the final frame, so it is rendered after the crop
+        let post_crop = filters
+            .into_iter()
+            .find(|filter| filter.filtertype == FilterType::Vignette);
+        if let Some(filter) = post_crop.filter(|filter| !filter.is_default()) {
+            out = self.render_vignette(out, &filter.parameters, frame)?;
+        }
+
+        Ok(out)
+    }
+
+    // `frame` is the rectangle of `img` the vignette is centered in
+    fn render_vignette(
+        &mut self,
+        mut img: CRgbaImage<P>,
+        parameters: &[f32],
+        frame: (u32, u32, u32, u32),
+    ) -> Result<CRgbaImage<P>, crate::core::RenderingError<'static>> {
+        if self.backend == RenderingBackends::CPU {
+            vignette(&mut img, parameters, frame);
+            return Ok(img);
+        }
+
+        let mut parameters = parameters.to_vec();
+        parameters.extend([frame.0, frame.1, frame.2, frame.3].map(|v| v as f32));
+        let gpu = self.gpu.as_mut().unwrap();
+        gpu.create_rgb_texture(&img)
+            .expect("Failed to create a texture!");
+        gpu.render(&GPUShaderType::Vignette, &parameters)
+            .map_err(RenderingError::GPUERROR)
+    }
+
+    // the imagebuffers must contain `img` when this function is called
+    fn render_stage(
+        &mut self,
+        img: &CRgbaImage<P>,
+        filter: &Filter,
+    ) -> Result<CRgbaImage<P>, crate::core::RenderingError<'static>> {
+        let gpu_filter: Option<GPUShaderType> = {
+            match filter.filtertype {
+                FilterType::Saturation => Some(LVIE_GPU::GPUShaderType::Saturation),
+                FilterType::Exposition => Some(LVIE_GPU::GPUShaderType::Exposition),
+                FilterType::WhiteBalance => Some(LVIE_GPU::GPUShaderType::WhiteBalance),
+                FilterType::ChannelMixer => Some(LVIE_GPU::GPUShaderType::ChannelMixer),
+                FilterType::BlackAndWhite => Some(LVIE_GPU::GPUShaderType::BlackAndWhite),
+                _ => None,
+            }
+        };
+
+        let out: CRgbaImage<P>;
+
+        if self.backend == RenderingBackends::GPU && gpu_filter.is_some() {
+            let parameters = match filter.filtertype {
+                FilterType::WhiteBalance => {
+                    let from = filter.filtertype.default();
+                    vec![from[0], from[1], filter.parameters[0], filter.parameters[1]]
+                }
+                _ => filter.parameters.clone(),
+            };
+
+            let gpu = self.gpu.as_mut().unwrap();
+            gpu.create_rgb_texture(img)
+                .expect("Failed to create a texture!");
+            let res = gpu.render(&gpu_filter.unwrap(), &parameters);
+            if res.is_err() {
+                return Err(RenderingError::GPUERROR(res.unwrap_err()));
+            } else {
+                out = res.unwrap();
+            }
+            self.imagebuffers.replace_rgb(out.clone());
+        } else {
+            match filter.filtertype {
+                FilterType::Saturation => {
+                    saturate(
+                        self.imagebuffers.get_oklch_mut_updated(),
+                        filter.parameters[0],
+                    );
+                    self.imagebuffers.set_updated(false, false, false, true);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::HslMixer => {
+                    hsl_mixer(
+                        self.imagebuffers.get_oklch_mut_updated(),
+                        &filter.parameters,
+                    );
+                    self.imagebuffers.set_updated(false, false, false, true);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::ChannelMixer => {
+                    channel_mixer(self.imagebuffers.get_rgb_mut_updated(), &filter.parameters);
+                    self.imagebuffers.set_updated(true, false, false, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::BlackAndWhite => {
+                    black_and_white(
+                        self.imagebuffers.get_oklch_mut_updated(),
+                        &filter.parameters,
+                    );
+                    self.imagebuffers.set_updated(false, false, false, true);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::ColorGrading => {
+                    color_grading(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        &filter.parameters,
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::Exposition => {
+                    exposition(
+                        self.imagebuffers.get_hsl_mut_updated(),
+                        filter.parameters[0],
+                    );
+                    self.imagebuffers.set_updated(false, true, false, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::Boxblur => {
+                    out = FastBoxBlur(img, filter.parameters[0] as u32);
+                    self.imagebuffers.replace_rgb(out.clone());
+                }
+                FilterType::Sharpening => {
+                    sharpen(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        filter.parameters[0],
+                        filter.parameters[1] as usize,
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::GaussianBlur => {
+                    out = FastGaussianBlur(img, filter.parameters[0], filter.parameters[1] as u8);
+                    self.imagebuffers.replace_rgb(out.clone());
+                }
+                FilterType::WhiteBalance => {
+                    // the image is always rendered from the original, so the source
+                    // white point is the neutral one
+                    let from = filter.filtertype.default();
+                    whitebalance(
+                        self.imagebuffers.get_rgb_mut_updated(),
+                        from[0],
+                        from[1],
+                        filter.parameters[0],
+                        filter.parameters[1],
+                    );
+                    self.imagebuffers.set_updated(true, false, false, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::HighlightsShadows => {
+                    shadows_and_highlights(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        filter.parameters[0],
+                        filter.parameters[1],
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::WhitesBlacks => {
+                    whites_and_blacks(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        filter.parameters[0],
+                        filter.parameters[1],
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::WaveletDenoise => {
+                    wavelet_denoise(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        &filter.parameters,
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::LocalContrast => {
+                    local_contrast(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        filter.parameters[0],
+                        filter.parameters[1],
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::Dehaze => {
+                    dehaze(
+                        self.imagebuffers.get_rgb_mut_updated(),
+                        filter.parameters[0],
+                    );
+                    self.imagebuffers.set_updated(true, false, false, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::Grain => {
+                    grain(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        &filter.parameters,
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
+                }
+                FilterType::Vignette => {
+                    // inside a mask the frame is the whole image
+                    out = self.render_vignette(
+                        img.clone(),
+                        &filter.parameters,
+                        (0, 0, img.width(), img.height()),
+                    )?;
+                    self.imagebuffers.replace_rgb(out.clone());
+                }
+                FilterType::Contrast => {
+                    let algorithm = match filter.parameters[1] as u8 {
+                        1 => ContrastAlgorithm::HistogramEqualize,
+                        2 => ContrastAlgorithm::SCurve(filter.parameters[2]),
+                        _ => ContrastAlgorithm::Linear,
+                    };
+                    contrast(
+                        self.imagebuffers.get_oklab_mut_updated(),
+                        filter.parameters[0],
+                        algorithm,
+                    );
+                    self.imagebuffers.set_updated(false, false, true, false);
+                    out = self.imagebuffers.get_rgb_updated().clone();
                 }
             }
         }
@@ -162,6 +438,45 @@ where
         Ok(out)
     }
 
+    // returns the output of the last stage before `stage` that modified the image
+    fn cached_output(&self, stage: usize) -> Option<CRgbaImage<P>> {
+        self.cache[..stage]
+            .iter()
+            .rev()
+            .find_map(|(_, img)| img.clone())
+    }
+
+    // the image after the lens correction, the rotation and the perspective of the last render,
+    // `img` itself if they leave it unchanged. `None` if the last render used another geometry
+    pub fn geometric_output<'a>(
+        &'a self,
+        img: &'a CRgbaImage<P>,
+        geometry: &Geometry,
+    ) -> Option<&'a CRgbaImage<P>> {
+        let stages = [
+            Stage::Lens(geometry.lens),
+            Stage::Rotation(geometry.rotation),
+            Stage::Perspective(geometry.perspective),
+        ];
+        if stages.iter().all(|stage| stage.is_noop()) {
+            return Some(img);
+        }
+
+        let cached = self.cache.get(..stages.len())?;
+        if cached
+            .iter()
+            .zip(&stages)
+            .any(|((cached, _), stage)| cached != stage)
+        {
+            return None;
+        }
+        cached.iter().rev().find_map(|(_, img)| img.as_ref())
+    }
+
+    pub fn clear_cache(&mut self) {
+        self.cache.clear();
+    }
+
     pub fn attach_image_buffers(&mut self, imagebuffers: ImageBuffers<P>) {
         self.imagebuffers = imagebuffers;
     }
@@ -186,6 +501,49 @@ where
             backend: self.backend.clone(),
             gpu,
             imagebuffers: self.imagebuffers.clone(),
+            cache: self.cache.clone(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometric_output_follows_the_last_render() {
+        let mut rendering: Rendering<image::Rgba<u8>> = Rendering::init(RenderingBackends::CPU);
+        let img = CRgbaImage::<image::Rgba<u8>>::new(6, 4);
+        let geometry = Geometry {
+            rotation: Rotation::default().turned(true),
+            ..Geometry::default()
+        };
+
+        // nothing is rendered yet, but without a geometry the loaded image is enough
+        assert!(rendering.geometric_output(&img, &geometry).is_none());
+        assert!(std::ptr::eq(
+            rendering
+                .geometric_output(&img, &Geometry::default())
+                .unwrap(),
+            &img
+        ));
+
+        rendering
+            .render_data(
+                &img,
+                &geometry,
+                &FilterArray::new(None),
+                &ToneCurves::new(),
+                &[],
+            )
+            .unwrap();
+        let rotated = rendering.geometric_output(&img, &geometry).unwrap();
+        assert_eq!(rotated.dimensions(), (4, 6));
+
+        let other = Geometry {
+            rotation: Rotation::default().turned(false),
+            ..geometry
+        };
+        assert!(rendering.geometric_output(&img, &other).is_none());
+    }
+}
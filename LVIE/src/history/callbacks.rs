@@ -4,12 +4,15 @@ use slint::{ComponentHandle, Rgba8Pixel, SharedPixelBuffer, Weak};
 use LVIElib::traits::ScaleImage;
 
 use crate::{
-    core::Mask,
+    core::{
+        callbacks::{show_crop, show_lens, show_mask, show_perspective, show_rotation},
+        insert_mask, remove_mask, Mask,
+    },
     history::{History, *},
 };
 
 use super::super::{
-    ui::{ScreenCallbacks, LVIE},
+    ui::{CurveCallbacks, ScreenCallbacks, LVIE},
     Data,
 };
 
@@ -53,19 +56,12 @@ pub fn init_history_callbacks<P>(
                     let (from, _) = nop.get_content();
                     data.update_filters(from.clone());
 
-                    let exp = from.get_filter(crate::core::FilterType::Exposition)[0];
-                    let bb = from.get_filter(crate::core::FilterType::Boxblur)[0];
-                    let gb = from.get_filter(crate::core::FilterType::GaussianBlur)[0];
-                    let sh = from.get_filter(crate::core::FilterType::Sharpening)[0];
-                    let temp_and_tint = from.get_filter(crate::core::FilterType::WhiteBalance);
-                    let sat = from.get_filter(crate::core::FilterType::Saturation)[0];
-
-                    parameters = vec![exp, bb, gb, sh, temp_and_tint[0], temp_and_tint[1], sat];
+                    if !data.mask_adjustments {
+                        parameters = from.to_slider_values();
+                    }
 
                     if history.preview_aviable() {
-                        data.norm_filters();
                         data.full_res_preview = history.get_precomputed_preview().unwrap().unwrap();
-                        data.manual_reset_rendering();
                         data.full_res_preview.clone()
                     } else {
                         data.update_image()
@@ -75,20 +71,45 @@ pub fn init_history_callbacks<P>(
                     let nop = op.as_ref().downcast_ref::<GeometricOperation>().unwrap();
                     match nop.get_content() {
                         GeometricOperationType::Rotation(x) => {
-                            data.rotation -= x;
+                            data.rotation = data.rotation.turned(*x < 0.0);
+                            data.crop = data.crop.rotated_90(*x < 0.0);
+                            data.perspective = data.perspective.rotated_90(*x < 0.0);
+                            data.masks.iter_mut().for_each(|m| m.rotate_90(*x < 0.0));
+                            show_crop(&ww.unwrap(), &data.crop);
+                            show_perspective(&ww.unwrap(), &data.perspective);
+                            let selected = data.selected_mask;
+                            show_mask(&ww.unwrap(), &mut data.masks, selected);
                             if history.preview_aviable() {
-                                let out = history.get_precomputed_preview().unwrap().unwrap();
-                                data.load_image(out.clone(), false);
-                                out
+                                data.full_res_preview =
+                                    history.get_precomputed_preview().unwrap().unwrap();
+                                data.full_res_preview.clone()
                             } else {
-                                let new_image = image::imageops::rotate270(&data.full_res_preview);
-                                data.load_image(new_image.clone(), false);
-                                new_image
+                                data.update_image()
                             }
                         }
                         GeometricOperationType::Traslation(_ox, _oy) => {
                             todo!()
                         }
+                        &GeometricOperationType::Crop(old, _) => {
+                            data.crop = old;
+                            show_crop(&ww.unwrap(), &data.crop);
+                            data.update_image()
+                        }
+                        &GeometricOperationType::FreeRotation(old, _) => {
+                            data.rotation = old;
+                            show_rotation(&ww.unwrap(), &data.rotation);
+                            data.update_image()
+                        }
+                        &GeometricOperationType::Perspective(old, _) => {
+                            data.perspective = old;
+                            show_perspective(&ww.unwrap(), &data.perspective);
+                            data.update_image()
+                        }
+                        &GeometricOperationType::Lens(old, _) => {
+                            data.lens = old;
+                            show_lens(&ww.unwrap(), &data.lens);
+                            data.update_image()
+                        }
                     }
                 }
                 &OperationType::Logic => {
@@ -96,6 +117,9 @@ pub fn init_history_callbacks<P>(
                     match nop.get_content() {
                         LogicOperationType::Reset(filters) => {
                             data.update_filters(filters.clone());
+                            if !data.mask_adjustments {
+                                parameters = filters.to_slider_values();
+                            }
                             data.update_image()
                         }
                         LogicOperationType::FileLoaded() => {
@@ -127,54 +151,78 @@ pub fn init_history_callbacks<P>(
                             data.masks[*mask_number].add_point_at_index([ox, oy], index);
                         }
                         &MaskOperationType::MaskOpened(_, _) => {
-                            data.masks.remove(*mask_number);
+                            remove_mask(&mut data.masks, *mask_number);
                         }
                         MaskOperationType::MaskClosed() => {
                             data.masks[*mask_number].undo_close();
                         }
+                        &MaskOperationType::GradientMaskAdded(_)
+                        | &MaskOperationType::RangeMaskAdded(_)
+                        | &MaskOperationType::BrushMaskAdded() => {
+                            remove_mask(&mut data.masks, *mask_number);
+                        }
+                        MaskOperationType::StrokeAdded(_) => {
+                            data.masks[*mask_number].pop_stroke();
+                        }
+                        &MaskOperationType::RangeChanged(old, _) => {
+                            if let Some(range) = data.masks[*mask_number].range_mut() {
+                                *range = old;
+                            }
+                        }
+                        &MaskOperationType::HandleMoved(index, ox, oy, _, _) => {
+                            data.masks[*mask_number]
+                                .update_point(index, [ox, oy])
+                                .expect("Failed to move handle");
+                        }
+                        &MaskOperationType::FeatherChanged(old, _) => {
+                            data.masks[*mask_number].set_feather(old);
+                        }
+                        &MaskOperationType::DensityChanged(old, _) => {
+                            data.masks[*mask_number].set_density(old);
+                        }
+                        MaskOperationType::CombinationChanged(old, _) => {
+                            data.masks[*mask_number].set_combination(old.clone());
+                        }
+                        MaskOperationType::FiltersChanged(old, _) => {
+                            data.masks[*mask_number].update_filters(old.clone());
+                        }
                     }
 
-                    let W = ww.unwrap();
-                    W.set_mask_points(data.masks[0].into_rc_model());
-                    W.set_bezier_control_points(data.masks[0].get_control_points_model_rc());
-                    #[cfg(not(debug_assertions))]
-                    {
-                        W.set_connection_line_points(
-                            data.masks[0].generate_line_for_slint(None, None),
-                        );
-                        W.set_control_point_connection_line(
-                            data.masks[0].generate_control_point_connection_lines_for_slint(),
-                        );
-                    }
+                    data.selected_mask = (*mask_number).min(data.masks.len() - 1);
+                    let selected = data.selected_mask;
+                    show_mask(&ww.unwrap(), &mut data.masks, selected);
 
-                    return;
+                    if data.mask_adjustments {
+                        parameters = data.current_mask().get_filters().to_slider_values();
+                    }
+                    data.update_image()
                 }
                 &OperationType::Curve => {
                     let nop = op.as_ref().downcast_ref::<CurveOperation>().unwrap();
-                    match nop.get_content() {
+                    let (channel, content) = nop.get_content();
+                    let curve = data.curves.get_mut(*channel);
+
+                    match content {
                         &CurveOperationType::CurvePointAdded(index, _, _) => {
-                            data.curve
-                                .remove_point(index)
-                                .expect("Failed to remove point");
+                            curve.remove_point(index).expect("Failed to remove point");
                         }
                         &CurveOperationType::CurvePointMoved(index, ox, oy, _, _) => {
-                            data.curve
+                            curve
                                 .update_curve_point(index, [ox, oy])
                                 .expect("Failed to set point value");
                         }
                         &CurveOperationType::CurvePointRemoved(_, ox, oy) => {
-                            data.curve.add_point([ox, oy]).expect("Failed to add point");
+                            curve.add_point([ox, oy]).expect("Failed to add point");
                         }
-                        &CurveOperationType::CurveTypeChanged(c_type) => {
-                            data.curve.set_curve_type(c_type);
+                        &CurveOperationType::CurveTypeChanged(old_type, _) => {
+                            curve.set_curve_type(old_type);
                         }
                     }
 
-                    let W = ww.unwrap();
-                    W.set_curve(data.curve.to_image((300, 300)));
-                    W.set_curve_points(data.curve.into_rc_model());
+                    data.curve_channel = *channel;
+                    show_curve(&ww.unwrap(), &data);
 
-                    return;
+                    data.update_image()
                 }
             };
 
@@ -215,27 +263,12 @@ pub fn init_history_callbacks<P>(
                     let (_, to) = nop.get_content();
                     data.update_filters(to.clone());
 
-                    let exp = to.get_filter(crate::core::FilterType::Exposition)[0];
-                    let bb: f32 = to.get_filter(crate::core::FilterType::Boxblur)[0];
-                    let gb = to.get_filter(crate::core::FilterType::GaussianBlur)[0];
-                    let sh = to.get_filter(crate::core::FilterType::Sharpening)[0];
-                    let temp_and_tint = to.get_filter(crate::core::FilterType::WhiteBalance);
-                    let sat = to.get_filter(crate::core::FilterType::Saturation)[0];
-
-                    parameters = vec![
-                        exp,
-                        bb,
-                        gb,
-                        sh,
-                        temp_and_tint[0] / 2000f32 - 6000f32,
-                        temp_and_tint[1] / 50f32,
-                        sat,
-                    ];
+                    if !data.mask_adjustments {
+                        parameters = to.to_slider_values();
+                    }
 
                     if history.preview_aviable() {
-                        data.norm_filters();
                         data.full_res_preview = history.get_precomputed_preview().unwrap().unwrap();
-                        data.manual_reset_rendering();
                         data.full_res_preview.clone()
                     } else {
                         data.update_image()
@@ -245,20 +278,45 @@ pub fn init_history_callbacks<P>(
                     let nop = op.as_ref().downcast_ref::<GeometricOperation>().unwrap();
                     match nop.get_content() {
                         GeometricOperationType::Rotation(x) => {
-                            data.rotation += x;
+                            data.rotation = data.rotation.turned(*x > 0.0);
+                            data.crop = data.crop.rotated_90(*x > 0.0);
+                            data.perspective = data.perspective.rotated_90(*x > 0.0);
+                            data.masks.iter_mut().for_each(|m| m.rotate_90(*x > 0.0));
+                            show_crop(&ww.unwrap(), &data.crop);
+                            show_perspective(&ww.unwrap(), &data.perspective);
+                            let selected = data.selected_mask;
+                            show_mask(&ww.unwrap(), &mut data.masks, selected);
                             if history.preview_aviable() {
-                                let out = history.get_precomputed_preview().unwrap().unwrap();
-                                data.load_image(out.clone(), false);
-                                out
+                                data.full_res_preview =
+                                    history.get_precomputed_preview().unwrap().unwrap();
+                                data.full_res_preview.clone()
                             } else {
-                                let new_image = image::imageops::rotate90(&data.full_res_preview);
-                                data.load_image(new_image.clone(), false);
-                                new_image
+                                data.update_image()
                             }
                         }
                         GeometricOperationType::Traslation(_ox, _oy) => {
                             todo!()
                         }
+                        &GeometricOperationType::Crop(_, new) => {
+                            data.crop = new;
+                            show_crop(&ww.unwrap(), &data.crop);
+                            data.update_image()
+                        }
+                        &GeometricOperationType::FreeRotation(_, new) => {
+                            data.rotation = new;
+                            show_rotation(&ww.unwrap(), &data.rotation);
+                            data.update_image()
+                        }
+                        &GeometricOperationType::Perspective(_, new) => {
+                            data.perspective = new;
+                            show_perspective(&ww.unwrap(), &data.perspective);
+                            data.update_image()
+                        }
+                        &GeometricOperationType::Lens(_, new) => {
+                            data.lens = new;
+                            show_lens(&ww.unwrap(), &data.lens);
+                            data.update_image()
+                        }
                     }
                 }
                 &OperationType::Logic => {
@@ -266,6 +324,9 @@ pub fn init_history_callbacks<P>(
                     match nop.get_content() {
                         LogicOperationType::Reset(_) => {
                             data.reset();
+                            if !data.mask_adjustments {
+                                parameters = data.get_filters().to_slider_values();
+                            }
                             data.full_res_preview.clone()
                         }
                         LogicOperationType::FileLoaded() => {
@@ -299,54 +360,86 @@ pub fn init_history_callbacks<P>(
                         &MaskOperationType::MaskOpened(x, y) => {
                             let mut m = Mask::new();
                             m.add_point([x, y]);
-                            data.masks.insert(*mask_number, m);
+                            insert_mask(&mut data.masks, *mask_number, m);
                         }
                         MaskOperationType::MaskClosed() => {
-                            data.masks[*mask_number].undo_close();
+                            data.masks[*mask_number].close();
+                        }
+                        &MaskOperationType::GradientMaskAdded(gradient) => {
+                            insert_mask(
+                                &mut data.masks,
+                                *mask_number,
+                                Mask::from_gradient(gradient),
+                            );
+                        }
+                        &MaskOperationType::RangeMaskAdded(range) => {
+                            insert_mask(&mut data.masks, *mask_number, Mask::from_range(range));
+                        }
+                        &MaskOperationType::BrushMaskAdded() => {
+                            insert_mask(&mut data.masks, *mask_number, Mask::brush());
+                        }
+                        MaskOperationType::StrokeAdded(stroke) => {
+                            data.masks[*mask_number].push_stroke(stroke.clone());
+                        }
+                        &MaskOperationType::RangeChanged(_, new) => {
+                            if let Some(range) = data.masks[*mask_number].range_mut() {
+                                *range = new;
+                            }
+                        }
+                        &MaskOperationType::HandleMoved(index, _, _, x, y) => {
+                            data.masks[*mask_number]
+                                .update_point(index, [x, y])
+                                .expect("Failed to move handle");
+                        }
+                        &MaskOperationType::FeatherChanged(_, new) => {
+                            data.masks[*mask_number].set_feather(new);
+                        }
+                        &MaskOperationType::DensityChanged(_, new) => {
+                            data.masks[*mask_number].set_density(new);
+                        }
+                        MaskOperationType::CombinationChanged(_, new) => {
+                            data.masks[*mask_number].set_combination(new.clone());
+                        }
+                        MaskOperationType::FiltersChanged(_, new) => {
+                            data.masks[*mask_number].update_filters(new.clone());
                         }
                     }
 
-                    let W = ww.unwrap();
-                    W.set_mask_points(data.masks[0].into_rc_model());
-                    W.set_bezier_control_points(data.masks[0].get_control_points_model_rc());
-                    #[cfg(not(debug_assertions))]
-                    {
-                        W.set_connection_line_points(
-                            data.masks[0].generate_line_for_slint(None, None),
-                        );
-                        W.set_control_point_connection_line(
-                            data.masks[0].generate_control_point_connection_lines_for_slint(),
-                        );
-                    }
+                    data.selected_mask = (*mask_number).min(data.masks.len() - 1);
+                    let selected = data.selected_mask;
+                    show_mask(&ww.unwrap(), &mut data.masks, selected);
 
-                    return;
+                    if data.mask_adjustments {
+                        parameters = data.current_mask().get_filters().to_slider_values();
+                    }
+                    data.update_image()
                 }
                 &OperationType::Curve => {
                     let nop = op.as_ref().downcast_ref::<CurveOperation>().unwrap();
-                    match nop.get_content() {
+                    let (channel, content) = nop.get_content();
+                    let curve = data.curves.get_mut(*channel);
+
+                    match content {
                         &CurveOperationType::CurvePointAdded(_, x, y) => {
-                            data.curve.add_point([x, y]).expect("Failed to add point");
+                            curve.add_point([x, y]).expect("Failed to add point");
                         }
                         &CurveOperationType::CurvePointMoved(index, _, _, x, y) => {
-                            data.curve
+                            curve
                                 .update_curve_point(index, [x, y])
                                 .expect("Failed to set point value");
                         }
                         &CurveOperationType::CurvePointRemoved(index, _, _) => {
-                            data.curve
-                                .remove_point(index)
-                                .expect("Failed to remove point");
+                            curve.remove_point(index).expect("Failed to remove point");
                         }
-                        &CurveOperationType::CurveTypeChanged(c_type) => {
-                            data.curve.set_curve_type(c_type);
+                        &CurveOperationType::CurveTypeChanged(_, new_type) => {
+                            curve.set_curve_type(new_type);
                         }
                     }
 
-                    let W = ww.unwrap();
-                    W.set_curve(data.curve.to_image((300, 300)));
-                    W.set_curve_points(data.curve.into_rc_model());
+                    data.curve_channel = *channel;
+                    show_curve(&ww.unwrap(), &data);
 
-                    return;
+                    data.update_image()
                 }
             };
 
@@ -370,3 +463,31 @@ pub fn init_history_callbacks<P>(
         }
     });
 }
+
+// shows the curve of the selected channel in the curve editor
+fn show_curve<P>(Window: &LVIE, data: &Data<P>)
+where
+    P: image::Pixel
+        + Send
+        + Sync
+        + std::fmt::Debug
+        + LVIElib::traits::ToHsl
+        + LVIElib::traits::ToOklab
+        + 'static,
+    P::Subpixel: LVIElib::traits::Scale
+        + image::Primitive
+        + std::fmt::Debug
+        + bytemuck::Pod
+        + Send
+        + Sync
+        + num_traits::ToBytes
+        + LVIElib::traits::AsFloat,
+{
+    let curve = data.selected_curve();
+    Window.set_curve(curve.to_image((300, 300)));
+    Window.set_curve_points(curve.into_rc_model());
+
+    let callbacks = Window.global::<CurveCallbacks>();
+    callbacks.set_current_channel(data.curve_channel.index() as i32);
+    callbacks.set_current_curve_type(*curve.get_curve_type() as i32);
+}
@@ -16,7 +16,7 @@ pub use callbacks::init_history_callbacks;
 use itertools::Itertools;
 pub use operations::*;
 
-use crate::core::FilterArray;
+use crate::core::{CurveChannel, FilterArray};
 
 use image::Primitive;
 use num_traits::NumCast;
@@ -28,7 +28,7 @@ build_operation!(
     (Logic, LogicOperationType),
     (Geometric, GeometricOperationType),
     (Mask, (usize, MaskOperationType)),
-    (Curve, CurveOperationType),
+    (Curve, (CurveChannel, CurveOperationType)),
 );
 
 struct EmptyOperation {}
@@ -1,10 +1,18 @@
-use crate::core::{CurveType, FilterArray};
+use crate::core::{
+    BrushStroke, Crop, CurveType, FilterArray, GradientMask, LensModel, MaskTree, Perspective,
+    RangeMask, Rotation,
+};
 
 #[derive(Debug, Clone)]
 #[non_exhaustive]
 pub enum GeometricOperationType {
     Rotation(f32),
     Traslation(f32, f32),
+    Crop(Crop, Crop),
+    // free angle, interpolation and auto crop, before and after
+    FreeRotation(Rotation, Rotation),
+    Perspective(Perspective, Perspective),
+    Lens(LensModel, LensModel),
 }
 
 #[derive(Debug, Clone)]
@@ -25,6 +33,17 @@ pub enum MaskOperationType {
 
     MaskOpened(f32, f32),
     MaskClosed(),
+
+    GradientMaskAdded(GradientMask),
+    RangeMaskAdded(RangeMask),
+    RangeChanged(RangeMask, RangeMask),
+    BrushMaskAdded(),
+    StrokeAdded(BrushStroke),
+    HandleMoved(usize, f32, f32, f32, f32),
+    FeatherChanged(f32, f32),
+    DensityChanged(f32, f32),
+    CombinationChanged(Option<MaskTree>, Option<MaskTree>),
+    FiltersChanged(FilterArray, FilterArray),
 }
 
 #[derive(Debug, Clone)]
@@ -33,5 +52,5 @@ pub enum CurveOperationType {
     CurvePointAdded(usize, f32, f32),
     CurvePointRemoved(usize, f32, f32),
     CurvePointMoved(usize, f32, f32, f32, f32),
-    CurveTypeChanged(CurveType),
+    CurveTypeChanged(CurveType, CurveType),
 }
@@ -1,9 +1,10 @@
 #![allow(dead_code)]
-use image::{ImageBuffer, Pixel, Primitive, Rgb, RgbImage, Rgba, RgbaImage};
+use image::{GenericImageView, ImageBuffer, Pixel, Primitive, Rgb, RgbImage, Rgba, RgbaImage};
 use rayon::prelude::*;
 use std::collections::HashMap;
 use std::hash::Hash;
 use std::sync::{Arc, Mutex};
+use LVIElib::auto_tone::lightness_histogram;
 use LVIElib::hsl::{Hsl, Hsla};
 use LVIElib::linear_srgb::{LinSrgb, LinSrgba};
 use LVIElib::matrix::{
@@ -13,6 +14,7 @@ use LVIElib::utils::{convert_hsla_to_rgba, convert_rgba_to_hsla};
 use LVIElib::white_balance::{xyz_wb_matrix, LINSRGB_TO_XYZ, XYZ_TO_LINSRGB};
 
 use LVIElib::oklab::{Oklab, OklabImage, Oklaba, OklabaImage};
+use LVIElib::traits::{Scale, ToOklab};
 
 use LVIElib::matrix::convolution::convolve;
 
@@ -106,6 +108,25 @@ where
     [r, g, b]
 }
 
+// histograms of the red, green and blue channels scaled to 8 bits and of the OkLab lightness,
+// computed pixel by pixel so the image is not copied
+pub fn collect_tone_histograms<I>(img: &I) -> ([Vec<u32>; 3], Vec<u32>)
+where
+    I: GenericImageView,
+    I::Pixel: ToOklab,
+    <I::Pixel as Pixel>::Subpixel: Scale,
+{
+    let mut rgb = [vec![0u32; 256], vec![0u32; 256], vec![0u32; 256]];
+    for (_, _, pixel) in img.pixels() {
+        for (histogram, c) in rgb.iter_mut().zip(pixel.channels()) {
+            histogram[c.scale::<u8>() as usize] += 1;
+        }
+    }
+    let lightness = lightness_histogram(img.pixels().map(|(_, _, p)| *p.to_oklaba().l()));
+
+    (rgb, lightness)
+}
+
 pub fn _collect_histogram_data_old<P>(
     img: &ImageBuffer<P, Vec<P::Subpixel>>,
 ) -> [HashMap<P::Subpixel, u32>; 3]
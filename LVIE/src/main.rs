@@ -74,7 +74,7 @@ fn main() {
 
     let d = Data::new(CORE, None, None);
 
-    let curves = &d.curve;
+    let curves = d.selected_curve();
     Window.set_curve(curves.to_image((300, 300)));
     Window.set_curve_points(curves.into_rc_model());
 
@@ -129,6 +129,8 @@ fn main() {
 
     init_mask_callbacks(Window.as_weak(), DATA.clone(), HISTORY.clone());
 
+    init_geometry_callbacks(Window.as_weak(), DATA.clone(), HISTORY.clone());
+
     init_history_callbacks(Window.as_weak(), DATA.clone(), HISTORY.clone());
 
     // startup procedure
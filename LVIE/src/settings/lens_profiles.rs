@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use std::io::prelude::*;
+
+use LVIElib::lens_correction::LensModel;
+
+const PROFILES_FILE: &str = ".LVIE/lens_profiles.xml";
+
+// the parameters of a lens model stored with a name, the coefficients are described in `LensModel`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct LensProfile {
+    #[serde(rename = "@name")]
+    pub name: String,
+    #[serde(rename = "@k1")]
+    k1: f32,
+    #[serde(rename = "@k2")]
+    k2: f32,
+    #[serde(rename = "@k3")]
+    k3: f32,
+    #[serde(rename = "@v1")]
+    v1: f32,
+    #[serde(rename = "@v2")]
+    v2: f32,
+    #[serde(rename = "@v3")]
+    v3: f32,
+    #[serde(rename = "@red")]
+    red: f32,
+    #[serde(rename = "@blue")]
+    blue: f32,
+}
+
+impl LensProfile {
+    pub fn new(name: String, lens: &LensModel) -> LensProfile {
+        let [k1, k2, k3] = lens.distortion;
+        let [v1, v2, v3] = lens.vignetting;
+        let [red, blue] = lens.chromatic_aberration;
+        LensProfile {
+            name,
+            k1,
+            k2,
+            k3,
+            v1,
+            v2,
+            v3,
+            red,
+            blue,
+        }
+    }
+
+    pub fn model(&self) -> LensModel {
+        LensModel {
+            distortion: [self.k1, self.k2, self.k3],
+            vignetting: [self.v1, self.v2, self.v3],
+            chromatic_aberration: [self.red, self.blue],
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct LensProfiles {
+    #[serde(rename = "profile", default)]
+    profiles: Vec<LensProfile>,
+}
+
+impl LensProfiles {
+    pub fn names(&self) -> Vec<String> {
+        self.profiles.iter().map(|p| p.name.clone()).collect()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&LensProfile> {
+        self.profiles.get(index)
+    }
+
+    // a profile with the same name is replaced
+    pub fn insert(&mut self, profile: LensProfile) {
+        match self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            Some(p) => *p = profile,
+            None => self.profiles.push(profile),
+        }
+    }
+}
+
+pub fn load_lens_profiles() -> LensProfiles {
+    let Ok(content) = std::fs::read_to_string(PROFILES_FILE) else {
+        // there are no profiles until the first one is saved
+        return LensProfiles::default();
+    };
+
+    quick_xml::de::from_str(&content).unwrap_or_else(|_| {
+        eprintln!("cannot read the lens profiles, proceding without them...");
+        LensProfiles::default()
+    })
+}
+
+pub fn save_lens_profiles(profiles: &LensProfiles) -> std::io::Result<()> {
+    let content = quick_xml::se::to_string(profiles)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    std::fs::File::create(PROFILES_FILE)?.write_all(prettify_lens_xml(content).as_bytes())
+}
+
+fn prettify_lens_xml(content: String) -> String {
+    content
+        .replace("<profile", "\n\t<profile")
+        .replace("</LensProfiles>", "\n</LensProfiles>")
+}
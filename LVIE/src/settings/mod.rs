@@ -1,4 +1,5 @@
 pub mod keyboard_shortcuts;
+pub mod lens_profiles;
 
 use keyboard_shortcuts::{prettify_keyboard_xml, Keyboard};
 use serde::{Deserialize, Serialize};
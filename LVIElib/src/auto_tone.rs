@@ -0,0 +1,222 @@
+// OkLab lightness of the median of a well exposed image
+const TARGET_MEDIAN: f32 = 0.55;
+// standard deviation of the lightness of an image with a normal contrast
+const TARGET_DEVIATION: f32 = 0.2;
+// lightness of the white and the black points
+const WHITE_POINT: f32 = 0.96;
+const BLACK_POINT: f32 = 0.1;
+// fraction of the pixels ignored at both the ends of the histograms
+const CLIP: f32 = 0.005;
+
+// bins of the histogram of the lightness, from 0 to 1
+pub const LIGHTNESS_BINS: usize = 1024;
+
+/// the values proposed by `auto_tone`, they go from -1 to 1 like the sliders of the filters
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ToneAdjustments {
+    pub exposure: f32,
+    pub contrast: f32,
+    pub highlights: f32,
+    pub shadows: f32,
+    pub whites: f32,
+    pub blacks: f32,
+}
+
+// the value below which there is the fraction `q` of the samples of a histogram, in 0..1
+fn histogram_quantile(histogram: &[u32], q: f32) -> f32 {
+    let total: u64 = histogram.iter().map(|c| *c as u64).sum();
+    let target = (total as f64 * q as f64).ceil().max(1.0) as u64;
+    let mut count = 0;
+    for (i, c) in histogram.iter().enumerate() {
+        count += *c as u64;
+        if count >= target {
+            return i as f32 / (histogram.len() - 1) as f32;
+        }
+    }
+    1.0
+}
+
+/// histogram of the OkLab lightness of the pixels with `LIGHTNESS_BINS` bins,
+/// the values out of 0..1 go in the first and the last bin
+pub fn lightness_histogram(lightness: impl IntoIterator<Item = f32>) -> Vec<u32> {
+    let mut histogram = vec![0u32; LIGHTNESS_BINS];
+    for l in lightness {
+        histogram[(l.clamp(0.0, 1.0) * (LIGHTNESS_BINS - 1) as f32).round() as usize] += 1;
+    }
+    histogram
+}
+
+/// proposes the exposure, the contrast, the highlights, the shadows and the white and black points
+/// of an image. `histogram` are the histograms of the red, green and blue channels of the
+/// encoded image and `lightness` the histogram of its OkLab lightness, see `lightness_histogram`.
+/// The values are estimated in the order the filters are applied, every one on the image
+/// already changed by the previous ones
+pub fn auto_tone(histogram: &[Vec<u32>; 3], lightness: &[u32]) -> ToneAdjustments {
+    let total = lightness.iter().map(|c| *c as f32).sum::<f32>();
+    if lightness.len() < 2 || total == 0.0 || histogram.iter().any(|h| h.len() < 2) {
+        return ToneAdjustments::default();
+    }
+
+    // the lightness of every bin with its count, the adjustments below are monotonic,
+    // so they move the quantiles with the values
+    let bins: Vec<(f32, f32)> = lightness
+        .iter()
+        .enumerate()
+        .filter(|(_, c)| **c > 0)
+        .map(|(i, c)| (i as f32 / (lightness.len() - 1) as f32, *c as f32))
+        .collect();
+    let median = histogram_quantile(lightness, 0.5).max(1e-3);
+
+    // the exposure multiplies the encoded values, that are about L^(3 / 2.2)
+    let mut exposure = (3.0 / 2.2 * (TARGET_MEDIAN / median).log2()).clamp(-1.0, 1.0);
+    if exposure > 0.0 {
+        // the brightest channel must not be clipped
+        let brightest = histogram
+            .iter()
+            .map(|h| histogram_quantile(h, 1.0 - CLIP))
+            .fold(0.0, f32::max);
+        exposure = exposure.min((1.0 / brightest.max(1e-3)).log2());
+    }
+    let gain = 2f32.powf(exposure * 2.2 / 3.0);
+    let exposed = |l: f32| (l * gain).min(1.0);
+
+    // the linear contrast scales the distance from the mean
+    let mean = bins.iter().map(|(l, c)| c * exposed(*l)).sum::<f32>() / total;
+    let deviation = (bins
+        .iter()
+        .map(|(l, c)| c * (exposed(*l) - mean).powi(2))
+        .sum::<f32>()
+        / total)
+        .sqrt();
+    let contrast = (0.5 * (TARGET_DEVIATION / deviation.max(1e-3) - 1.0)).clamp(-0.5, 0.5);
+    let contrasted = |l: f32| ((1.0 + contrast) * (exposed(l) - mean) + mean).clamp(0.0, 1.0);
+
+    // the highlights are recovered and the shadows lifted by the area they cover
+    let fraction = |f: &dyn Fn(f32) -> bool| {
+        bins.iter()
+            .filter(|(l, _)| f(contrasted(*l)))
+            .map(|(_, c)| c)
+            .sum::<f32>()
+            / total
+    };
+    let highlights = -(2.0 * fraction(&|l| l > 0.75)).min(1.0) * 0.5;
+    let shadows = (2.0 * fraction(&|l| l < 0.3)).min(1.0) * 0.5;
+
+    // `whites_blacks` moves a value by a quarter of its weight, the squared distance from 0.5
+    let point = |value: f32, target: f32| {
+        let weight = ((value - 0.5) / 0.5).powi(2).max(0.05);
+        ((target - value) / (0.25 * weight)).clamp(-1.0, 1.0)
+    };
+    let whites = point(
+        contrasted(histogram_quantile(lightness, 1.0 - CLIP)),
+        WHITE_POINT,
+    );
+    let blacks = point(contrasted(histogram_quantile(lightness, CLIP)), BLACK_POINT);
+
+    ToneAdjustments {
+        exposure,
+        contrast,
+        highlights,
+        shadows,
+        whites,
+        blacks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // histograms and lightness of a gray image with the given lightness values
+    fn gray(lightness: &[f32]) -> ([Vec<u32>; 3], Vec<u32>) {
+        let mut histogram = vec![0u32; 256];
+        for l in lightness {
+            // the lightness is about the cube root of the linear light
+            let encoded = l.powi(3).powf(1.0 / 2.2);
+            histogram[(encoded * 255.0).round() as usize] += 1;
+        }
+        (
+            [histogram.clone(), histogram.clone(), histogram],
+            lightness_histogram(lightness.iter().copied()),
+        )
+    }
+
+    fn range(from: f32, to: f32) -> Vec<f32> {
+        (0..1000)
+            .map(|i| from + (to - from) * i as f32 / 999.0)
+            .collect()
+    }
+
+    #[test]
+    fn histogram_keeps_the_quantiles() {
+        let values = range(0.2, 0.9);
+        let histogram = lightness_histogram(values.iter().copied());
+        assert_eq!(histogram.iter().sum::<u32>(), 1000);
+        assert!((histogram_quantile(&histogram, 0.5) - values[499]).abs() < 2e-3);
+        assert!((histogram_quantile(&histogram, 0.1) - values[99]).abs() < 2e-3);
+    }
+
+    #[test]
+    fn dark_images_are_brightened() {
+        let (histogram, lightness) = gray(&range(0.05, 0.6));
+        let tone = auto_tone(&histogram, &lightness);
+        assert!(tone.exposure > 0.2);
+        assert!(tone.shadows > 0.0);
+    }
+
+    #[test]
+    fn bright_images_are_darkened() {
+        let (histogram, lightness) = gray(&range(0.6, 1.0));
+        let tone = auto_tone(&histogram, &lightness);
+        assert!(tone.exposure < -0.2);
+    }
+
+    #[test]
+    fn contrasty_images_recover_highlights_and_shadows() {
+        // a bright sky over a dark ground
+        let mut values = vec![0.15; 500];
+        values.extend(vec![0.92; 500]);
+        let (histogram, lightness) = gray(&values);
+        let tone = auto_tone(&histogram, &lightness);
+        assert!(tone.highlights < 0.0);
+        assert!(tone.shadows > 0.0);
+        assert!(tone.contrast < 0.0);
+    }
+
+    #[test]
+    fn exposure_does_not_clip_the_highlights() {
+        // mostly dark with a few bright pixels
+        let mut values = range(0.1, 0.3);
+        values.extend(vec![0.95; 100]);
+        let (histogram, lightness) = gray(&values);
+        let tone = auto_tone(&histogram, &lightness);
+        assert!(tone.exposure < 0.2);
+    }
+
+    #[test]
+    fn flat_images_get_more_contrast() {
+        let (histogram, lightness) = gray(&range(0.4, 0.7));
+        let tone = auto_tone(&histogram, &lightness);
+        assert!(tone.contrast > 0.2);
+        assert!(tone.whites > 0.0);
+        assert!(tone.blacks < 0.0);
+    }
+
+    #[test]
+    fn values_are_in_the_range_of_the_sliders() {
+        for values in [range(0.0, 0.05), range(0.98, 1.0), range(0.0, 1.0)] {
+            let (histogram, lightness) = gray(&values);
+            let tone = auto_tone(&histogram, &lightness);
+            for v in [
+                tone.exposure,
+                tone.contrast,
+                tone.highlights,
+                tone.shadows,
+                tone.whites,
+                tone.blacks,
+            ] {
+                assert!((-1.0..=1.0).contains(&v));
+            }
+        }
+    }
+}
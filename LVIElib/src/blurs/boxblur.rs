@@ -477,3 +477,46 @@ where
 
     CRgbaImage::<P>::from_raw(width, height, buf).unwrap()
 }
+
+// box blur of a single channel image stored row by row, the edges are extended
+pub fn box_blur_f32(data: &[f32], width: usize, height: usize, radius: usize) -> Vec<f32> {
+    if radius == 0 || data.is_empty() {
+        return data.to_vec();
+    }
+
+    // the vertical pass is an horizontal pass on the transposed image
+    let rows = blur_rows_f32(data, width, radius);
+    let columns = blur_rows_f32(&transpose_f32(&rows, width, height), height, radius);
+    transpose_f32(&columns, height, width)
+}
+
+fn blur_rows_f32(data: &[f32], width: usize, radius: usize) -> Vec<f32> {
+    let mut out = vec![0f32; data.len()];
+    let r = radius as isize;
+    let norm = 1.0 / (2 * radius + 1) as f64;
+
+    out.par_chunks_mut(width)
+        .zip(data.par_chunks(width))
+        .for_each(|(out_row, row)| {
+            let at = |i: isize| row[i.clamp(0, width as isize - 1) as usize] as f64;
+            let mut sum: f64 = (-r..=r).map(at).sum();
+            for (x, v) in out_row.iter_mut().enumerate() {
+                *v = (sum * norm) as f32;
+                sum += at(x as isize + r + 1) - at(x as isize - r);
+            }
+        });
+
+    out
+}
+
+fn transpose_f32(data: &[f32], width: usize, height: usize) -> Vec<f32> {
+    let mut out = vec![0f32; data.len()];
+    out.par_chunks_mut(height)
+        .enumerate()
+        .for_each(|(x, column)| {
+            for (y, v) in column.iter_mut().enumerate() {
+                *v = data[y * width + x];
+            }
+        });
+    out
+}
@@ -3,7 +3,7 @@ use image::{RgbImage, RgbaImage};
 
 use crate::traits::Scale;
 
-use super::boxblur::{CRgbaImage, FastBoxBlur, FastBoxBlur_rgb, FastBoxBlur_rgba};
+use super::boxblur::{box_blur_f32, CRgbaImage, FastBoxBlur, FastBoxBlur_rgb, FastBoxBlur_rgba};
 
 fn boxesForGauss(sigma: f32, n: f32) -> Vec<u16> // standard deviation, number of boxes
 {
@@ -72,6 +72,15 @@ where
     out
 }
 
+// gaussian blur of a single channel image stored row by row, approximated with 3 box blurs
+pub fn gaussian_blur_f32(data: &[f32], width: usize, height: usize, sigma: f32) -> Vec<f32> {
+    boxesForGauss(sigma, 3f32)
+        .into_iter()
+        .fold(data.to_vec(), |out, size| {
+            box_blur_f32(&out, width, height, ((size - 1) / 2) as usize)
+        })
+}
+
 /*
 fn gaussBlur_4 (img: &RgbImage, r: u32) {
     let bxs = boxesForGauss(r, 3);
@@ -0,0 +1,75 @@
+use super::boxblur::box_blur_f32;
+
+/// edge-preserving smoothing of `input` driven by `guide` (He et al.),
+/// both are single channel images of the same size stored row by row.
+/// Regions of the guide with a variance much lower than `eps` are smoothed, edges are kept
+pub fn guided_filter(
+    guide: &[f32],
+    input: &[f32],
+    width: usize,
+    height: usize,
+    radius: usize,
+    eps: f32,
+) -> Vec<f32> {
+    assert_eq!(guide.len(), width * height);
+    assert_eq!(input.len(), width * height);
+
+    let mean = |data: &[f32]| box_blur_f32(data, width, height, radius);
+    let product =
+        |a: &[f32], b: &[f32]| -> Vec<f32> { a.iter().zip(b).map(|(x, y)| x * y).collect() };
+
+    let mean_i = mean(guide);
+    let mean_p = mean(input);
+    let corr_ii = mean(&product(guide, guide));
+    let corr_ip = mean(&product(guide, input));
+
+    // coefficients of the local linear model q = a * I + b
+    let mut a = vec![0f32; guide.len()];
+    let mut b = vec![0f32; guide.len()];
+    for i in 0..guide.len() {
+        let var_i = corr_ii[i] - mean_i[i] * mean_i[i];
+        let cov_ip = corr_ip[i] - mean_i[i] * mean_p[i];
+        a[i] = cov_ip / (var_i + eps);
+        b[i] = mean_p[i] - a[i] * mean_i[i];
+    }
+
+    let mean_a = mean(&a);
+    let mean_b = mean(&b);
+    guide
+        .iter()
+        .zip(mean_a.iter().zip(mean_b))
+        .map(|(i, (a, b))| a * i + b)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_image_is_unchanged() {
+        let data = vec![0.3f32; 16 * 16];
+        let out = guided_filter(&data, &data, 16, 16, 3, 0.01);
+        assert!(out.iter().all(|v| (v - 0.3).abs() < 1e-5));
+    }
+
+    #[test]
+    fn keeps_edges_and_removes_noise() {
+        // a step edge with a small checkerboard noise on top
+        let data: Vec<f32> = (0..32 * 32)
+            .map(|i| {
+                let (x, y) = (i % 32, i / 32);
+                let step = if x < 16 { 0.2 } else { 0.8 };
+                step + if (x + y) % 2 == 0 { 0.01 } else { -0.01 }
+            })
+            .collect();
+        let out = guided_filter(&data, &data, 32, 32, 2, 0.001);
+
+        // the edge survives
+        assert!(out[16 * 32 + 14] < 0.3);
+        assert!(out[16 * 32 + 17] > 0.7);
+        // the noise is attenuated
+        let noise = (out[8 * 32 + 5] - out[8 * 32 + 6]).abs();
+        assert!(noise < 0.01);
+    }
+}
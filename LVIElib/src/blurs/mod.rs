@@ -1,2 +1,3 @@
 pub mod boxblur;
 pub mod gaussianblur;
+pub mod guidedfilter;
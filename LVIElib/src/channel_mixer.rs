@@ -0,0 +1,113 @@
+use crate::hsl_mixer::{band_weights, HUE_BANDS};
+use crate::oklch::Oklcha;
+
+// the rows of the 3x3 matrix, the offsets of red, green and blue and the preserve luminosity flag
+pub const PARAMETERS: usize = 13;
+
+// luminance of the linear sRGB primaries
+const LUMINANCE: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// mixes the channels of a color in linear light. Every output channel is the dot product of
+/// `rgb` with a row of the matrix in `parameters[..9]` plus its offset in `parameters[9..12]`.
+/// When `parameters[12]` is not 0 the output is scaled to keep the luminance of the input
+pub fn mix_channels(rgb: [f32; 3], parameters: &[f32]) -> [f32; 3] {
+    assert_eq!(parameters.len(), PARAMETERS);
+
+    let mut out: [f32; 3] = std::array::from_fn(|c| {
+        let row = &parameters[3 * c..3 * c + 3];
+        row[0] * rgb[0] + row[1] * rgb[1] + row[2] * rgb[2] + parameters[9 + c]
+    });
+
+    if parameters[12] != 0.0 {
+        let before: f32 = (0..3).map(|c| LUMINANCE[c] * rgb[c]).sum();
+        let after: f32 = (0..3).map(|c| LUMINANCE[c] * out[c]).sum();
+        if after > 1e-6 {
+            out.iter_mut().for_each(|v| *v *= before / after);
+        }
+    }
+    out
+}
+
+/// converts a color to gray. `weights` holds the change of the luminance of every hue band
+/// from -1 (one stop darker) to 1 (one stop brighter), so colors with the same lightness
+/// can become different grays
+pub fn black_and_white(pixel: &mut Oklcha, weights: &[f32]) {
+    assert_eq!(weights.len(), HUE_BANDS);
+
+    let exposure: f32 = band_weights(*pixel.h())
+        .iter()
+        .zip(weights)
+        .map(|(w, weight)| w * weight)
+        .sum();
+
+    // grays have no hue, so they keep their lightness
+    let colorfulness = (*pixel.c() / 0.1).min(1.0);
+
+    *pixel.l_mut() = (*pixel.l() * 2f32.powf(exposure * colorfulness)).min(1.0);
+    *pixel.c_mut() = 0.0;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hsl_mixer::BAND_CENTERS;
+
+    fn identity() -> Vec<f32> {
+        vec![1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0]
+    }
+
+    #[test]
+    fn identity_keeps_the_color() {
+        let rgb = [0.2, 0.5, 0.7];
+        assert_eq!(mix_channels(rgb, &identity()), rgb);
+    }
+
+    #[test]
+    fn swaps_and_offsets_the_channels() {
+        let mut parameters = identity();
+        // red from blue and blue from red
+        parameters[..9].copy_from_slice(&[0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.0]);
+        parameters[10] = 0.1;
+        let out = mix_channels([0.2, 0.5, 0.7], &parameters);
+        assert_eq!(out, [0.7, 0.6, 0.2]);
+    }
+
+    #[test]
+    fn preserves_the_luminosity() {
+        let mut parameters = identity();
+        // twice the red
+        parameters[0] = 2.0;
+        parameters[12] = 1.0;
+
+        let rgb = [0.4, 0.3, 0.2];
+        let out = mix_channels(rgb, &parameters);
+        let luminance = |c: [f32; 3]| (0..3).map(|i| LUMINANCE[i] * c[i]).sum::<f32>();
+        assert!((luminance(out) - luminance(rgb)).abs() < 1e-5);
+        assert!(out[0] / out[1] > rgb[0] / rgb[1]);
+    }
+
+    #[test]
+    fn black_and_white_removes_the_chroma() {
+        let mut pixel = Oklcha::new(0.6, 0.2, BAND_CENTERS[3], 1.0);
+        black_and_white(&mut pixel, &[0.0; HUE_BANDS]);
+        assert_eq!((*pixel.l(), *pixel.c()), (0.6, 0.0));
+    }
+
+    #[test]
+    fn hue_weights_change_the_gray() {
+        let mut weights = [0.0; HUE_BANDS];
+        // darker blues
+        weights[5] = -1.0;
+
+        let mut blue = Oklcha::new(0.5, 0.2, BAND_CENTERS[5], 1.0);
+        let mut red = Oklcha::new(0.5, 0.2, BAND_CENTERS[0], 1.0);
+        let mut gray = Oklcha::new(0.5, 0.0, BAND_CENTERS[5], 1.0);
+        for pixel in [&mut blue, &mut red, &mut gray] {
+            black_and_white(pixel, &weights);
+        }
+
+        assert!((*blue.l() - 0.25).abs() < 1e-5);
+        assert_eq!(*red.l(), 0.5);
+        assert_eq!(*gray.l(), 0.5);
+    }
+}
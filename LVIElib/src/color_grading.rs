@@ -0,0 +1,136 @@
+use crate::oklab::Oklaba;
+
+// shadows, midtones, highlights and global
+pub const ZONES: usize = 4;
+
+// hue, chroma and luminance of every zone, then balance and blending
+pub const PARAMETERS: usize = 3 * ZONES + 2;
+
+// OkLab chroma added to the zone when its chroma parameter is 1
+const MAX_CHROMA: f32 = 0.1;
+// lightness added to the zone when its luminance parameter is 1
+const MAX_LUMINANCE: f32 = 0.15;
+
+fn smoothstep(from: f32, to: f32, x: f32) -> f32 {
+    let t = ((x - from) / (to - from)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// how much the shadows, the midtones and the highlights affect a color with the given OkLab
+/// lightness, the weights always sum to 1.
+/// `balance` goes from -1 to 1 and moves the border between the shadows and the highlights,
+/// with positive values more of the image is a highlight. `blending` goes from 0 (hard
+/// borders) to 1 (the zones overlap over the whole range)
+pub fn zone_weights(lightness: f32, balance: f32, blending: f32) -> [f32; 3] {
+    let pivot = 0.5 - 0.3 * balance.clamp(-1.0, 1.0);
+    // the pivot is moved to 0.5
+    let l = lightness.clamp(0.0, 1.0);
+    let t = if l < pivot {
+        0.5 * l / pivot
+    } else {
+        0.5 + 0.5 * (l - pivot) / (1.0 - pivot)
+    };
+
+    let width = 0.25 + 0.5 * blending.clamp(0.0, 1.0);
+    let shadows = 1.0 - smoothstep(0.5 - width, 0.5, t);
+    let highlights = smoothstep(0.5, 0.5 + width, t);
+    [shadows, 1.0 - shadows - highlights, highlights]
+}
+
+/// grades the color of a pixel in OkLab. `parameters` holds the hue (in degrees), the chroma
+/// (from 0 to 1) and the luminance (from -1 to 1) of the shadows, the midtones, the highlights
+/// and the whole image, then the balance and the blending of the zones
+pub fn grade(pixel: &mut Oklaba, parameters: &[f32]) {
+    assert_eq!(parameters.len(), PARAMETERS);
+
+    let (balance, blending) = (parameters[3 * ZONES], parameters[3 * ZONES + 1]);
+    let [shadows, midtones, highlights] = zone_weights(*pixel.l(), balance, blending);
+
+    let (mut a, mut b, mut luminance) = (0f32, 0f32, 0f32);
+    for (w, zone) in [shadows, midtones, highlights, 1.0]
+        .iter()
+        .zip(parameters.chunks(3))
+    {
+        let (sin, cos) = zone[0].to_radians().sin_cos();
+        a += w * zone[1] * MAX_CHROMA * cos;
+        b += w * zone[1] * MAX_CHROMA * sin;
+        luminance += w * zone[2] * MAX_LUMINANCE;
+    }
+
+    *pixel.l_mut() = (*pixel.l() + luminance).clamp(0.0, 1.0);
+    *pixel.a_mut() += a;
+    *pixel.b_mut() += b;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn neutral() -> Vec<f32> {
+        let mut parameters = vec![0.0; PARAMETERS];
+        parameters[PARAMETERS - 1] = 0.5;
+        parameters
+    }
+
+    #[test]
+    fn weights_sum_to_one() {
+        for balance in [-1.0, 0.0, 0.7] {
+            for blending in [0.0, 0.5, 1.0] {
+                for l in 0..=100 {
+                    let w = zone_weights(l as f32 / 100.0, balance, blending);
+                    assert!((w.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+                    assert!(w.iter().all(|w| *w >= 0.0));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn zones_follow_the_lightness() {
+        assert_eq!(zone_weights(0.05, 0.0, 0.0)[0], 1.0);
+        assert_eq!(zone_weights(0.5, 0.0, 0.5)[1], 1.0);
+        assert_eq!(zone_weights(0.95, 0.0, 0.0)[2], 1.0);
+    }
+
+    #[test]
+    fn balance_moves_the_border() {
+        let l = 0.55;
+        assert!(zone_weights(l, 1.0, 0.0)[2] > zone_weights(l, 0.0, 0.0)[2]);
+        assert!(zone_weights(l, -1.0, 0.0)[0] > zone_weights(l, 0.0, 0.0)[0]);
+    }
+
+    #[test]
+    fn neutral_parameters_do_nothing() {
+        let mut pixel = Oklaba::new(0.4, 0.05, -0.02, 1.0);
+        grade(&mut pixel, &neutral());
+        assert_eq!(pixel, Oklaba::new(0.4, 0.05, -0.02, 1.0));
+    }
+
+    #[test]
+    fn shadows_are_tinted_and_highlights_are_not() {
+        // blue shadows with hard borders
+        let mut parameters = neutral();
+        parameters[0] = 264.0;
+        parameters[1] = 1.0;
+        parameters[PARAMETERS - 1] = 0.0;
+
+        let mut dark = Oklaba::new(0.1, 0.0, 0.0, 1.0);
+        let mut bright = Oklaba::new(0.95, 0.0, 0.0, 1.0);
+        grade(&mut dark, &parameters);
+        grade(&mut bright, &parameters);
+
+        assert!(*dark.b() < -0.09);
+        assert_eq!((*bright.a(), *bright.b()), (0.0, 0.0));
+    }
+
+    #[test]
+    fn global_luminance_moves_every_zone() {
+        let mut parameters = neutral();
+        parameters[3 * 3 + 2] = 1.0;
+        for l in [0.1, 0.5, 0.8] {
+            let mut pixel = Oklaba::new(l, 0.0, 0.0, 1.0);
+            grade(&mut pixel, &parameters);
+            assert!((*pixel.l() - l - MAX_LUMINANCE).abs() < 1e-5);
+        }
+    }
+}
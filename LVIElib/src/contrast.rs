@@ -4,11 +4,15 @@ use crate::{
     oklab::Oklab,
 };
 use image::Rgb;
+use rayon::prelude::*;
 use std::collections::HashMap;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ContrastAlgorithm {
     Linear,
     HistogramEqualize,
+    // sigmoidal curve centered on the pivot, the pivot value is left unchanged
+    SCurve(f32),
 }
 
 pub fn set_contrast(img: Matrix<u8>, c: f32) -> Matrix<u8> {
@@ -121,3 +125,135 @@ pub fn histogram_equalize(buf: Vec<u8>, c: f32) -> Vec<u8> {
 
     output
 }
+
+// applies the contrast to a luminance channel with values in 0..1 (like the OkLab L),
+// the amount goes from -1 (flat image) to 1
+pub fn apply_contrast(luma: &mut [f32], amount: f32, algorithm: ContrastAlgorithm) {
+    if amount == 0.0 || luma.is_empty() {
+        return;
+    }
+
+    match algorithm {
+        ContrastAlgorithm::Linear => {
+            let avg = luma.par_iter().sum::<f32>() / luma.len() as f32;
+            let c = 1.0 + amount;
+            luma.par_iter_mut().for_each(|l| *l = c * (*l - avg) + avg);
+        }
+        ContrastAlgorithm::HistogramEqualize => {
+            // negative amounts cannot be obtained by equalizing the histogram
+            histogram_equalize_f32(luma, amount.clamp(0.0, 1.0), 1024);
+        }
+        ContrastAlgorithm::SCurve(pivot) => {
+            let pivot = pivot.clamp(0.0, 1.0);
+            let strength = amount.clamp(-1.0, 1.0) * 10.0;
+            luma.par_iter_mut()
+                .for_each(|l| *l = sigmoidal_contrast(*l, strength, pivot));
+        }
+    }
+}
+
+// same as `histogram_equalize` but on floating point values in 0..1
+pub fn histogram_equalize_f32(buf: &mut [f32], c: f32, bins: usize) {
+    let to_bin = |v: f32| ((v.clamp(0.0, 1.0) * (bins - 1) as f32).round()) as usize;
+
+    let mut histogram = vec![0u32; bins];
+    for v in buf.iter() {
+        histogram[to_bin(*v)] += 1;
+    }
+
+    let mut cdf = histogram;
+    for i in 1..bins {
+        cdf[i] += cdf[i - 1];
+    }
+    let cdf_min = *cdf.iter().find(|x| **x != 0).unwrap_or(&0) as f32;
+    let size = buf.len() as f32;
+
+    if size == cdf_min {
+        // single valued image, there is nothing to equalize
+        return;
+    }
+
+    buf.par_iter_mut().for_each(|v| {
+        let eq = (cdf[to_bin(*v)] as f32 - cdf_min) / (size - cdf_min);
+        *v = (1.0 - c) * *v + c * eq;
+    });
+}
+
+// sigmoidal contrast normalized so that 0, the pivot and 1 are fixed points,
+// every side of the pivot is normalized on its own.
+// A negative strength applies the inverse curve
+pub fn sigmoidal_contrast(value: f32, strength: f32, pivot: f32) -> f32 {
+    if strength == 0.0 {
+        return value;
+    }
+
+    let k = strength.abs();
+    let sigmoid = |x: f32| 1.0 / (1.0 + (k * (pivot - x)).exp());
+    let (from, to) = if value <= pivot {
+        (0.0, pivot)
+    } else {
+        (pivot, 1.0)
+    };
+    if to <= from {
+        return value;
+    }
+    let (s_from, s_to) = (sigmoid(from), sigmoid(to));
+
+    if strength > 0.0 {
+        from + (to - from) * (sigmoid(value) - s_from) / (s_to - s_from)
+    } else {
+        let y = (s_from + (value - from) / (to - from) * (s_to - s_from))
+            .clamp(f32::EPSILON, 1.0 - f32::EPSILON);
+        pivot - (1.0 / y - 1.0).ln() / k
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_contrast, sigmoidal_contrast, ContrastAlgorithm};
+
+    #[test]
+    fn s_curve_fixed_points() {
+        for strength in [-10.0, -3.0, 3.0, 10.0] {
+            assert!(sigmoidal_contrast(0.0, strength, 0.4).abs() < 1e-5);
+            assert!((sigmoidal_contrast(1.0, strength, 0.4) - 1.0).abs() < 1e-5);
+        }
+
+        // the inverse curve undoes the direct one
+        let v = sigmoidal_contrast(0.3, 6.0, 0.5);
+        assert!((sigmoidal_contrast(v, -6.0, 0.5) - 0.3).abs() < 1e-4);
+        let v = sigmoidal_contrast(0.7, 6.0, 0.3);
+        assert!((sigmoidal_contrast(v, -6.0, 0.3) - 0.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn s_curve_keeps_the_pivot() {
+        for pivot in [0.1, 0.3, 0.45, 0.7, 0.9] {
+            for strength in [-10.0, -3.0, 3.0, 10.0] {
+                assert!((sigmoidal_contrast(pivot, strength, pivot) - pivot).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn s_curve_is_monotonic() {
+        for pivot in [0.0, 0.3, 0.8, 1.0] {
+            let mut last = sigmoidal_contrast(0.0, 10.0, pivot);
+            for i in 1..=100 {
+                let v = sigmoidal_contrast(i as f32 / 100.0, 10.0, pivot);
+                assert!(v >= last);
+                last = v;
+            }
+        }
+    }
+
+    #[test]
+    fn linear_keeps_the_average() {
+        let mut luma = vec![0.2, 0.4, 0.6, 0.8];
+        apply_contrast(&mut luma, 0.5, ContrastAlgorithm::Linear);
+
+        assert!((luma.iter().sum::<f32>() / 4.0 - 0.5).abs() < 1e-6);
+        assert!((luma[0] - 0.05).abs() < 1e-6);
+        assert!((luma[3] - 0.95).abs() < 1e-6);
+    }
+}
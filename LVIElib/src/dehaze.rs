@@ -0,0 +1,217 @@
+use std::collections::VecDeque;
+
+use crate::blurs::guidedfilter::guided_filter;
+
+// side of the patches of the dark channel relative to the longest side of the image
+pub const PATCH_RADIUS: f32 = 0.01;
+
+// fraction of the haze removed with strength 1, a bit of it is kept to preserve the depth
+const OMEGA: f32 = 0.95;
+// lower bound of the transmission, avoids amplifying the noise of the densest haze
+const MIN_TRANSMISSION: f32 = 0.1;
+// fraction of the pixels with the brightest dark channel used to estimate the atmospheric light
+const BRIGHTEST: f32 = 0.001;
+// regularization of the guided filter that refines the transmission
+const REFINE_EPS: f32 = 1e-3;
+
+// minimum of a line of `len` values spaced by `stride` over a window of 2 * radius + 1 values
+fn min_filter_line(data: &mut [f32], start: usize, len: usize, stride: usize, radius: usize) {
+    let line: Vec<f32> = (0..len).map(|i| data[start + i * stride]).collect();
+    // indices of the increasing minimums of the window
+    let mut window: VecDeque<usize> = VecDeque::new();
+    for i in 0..len + radius {
+        if i < len {
+            while window.back().is_some_and(|&j| line[j] >= line[i]) {
+                window.pop_back();
+            }
+            window.push_back(i);
+        }
+        if i >= radius {
+            let center = i - radius;
+            while window.front().is_some_and(|&j| j + radius < center) {
+                window.pop_front();
+            }
+            data[start + center * stride] = line[*window.front().unwrap()];
+        }
+    }
+}
+
+// separable minimum filter over square windows of side 2 * radius + 1
+fn min_filter(data: &mut [f32], width: usize, height: usize, radius: usize) {
+    for y in 0..height {
+        min_filter_line(data, y * width, width, 1, radius);
+    }
+    for x in 0..width {
+        min_filter_line(data, x, height, width, radius);
+    }
+}
+
+/// minimum of the channels of `pixels` divided by `light` over a square patch around every pixel.
+/// Outside of the haze almost every patch has a dark channel, so the dark channel measures the haze
+pub fn dark_channel(
+    pixels: &[[f32; 3]],
+    width: usize,
+    height: usize,
+    light: [f32; 3],
+    radius: usize,
+) -> Vec<f32> {
+    let mut dark: Vec<f32> = pixels
+        .iter()
+        .map(|p| {
+            (0..3)
+                .map(|c| p[c] / light[c].max(1e-3))
+                .fold(f32::MAX, f32::min)
+        })
+        .collect();
+    min_filter(&mut dark, width, height, radius);
+    dark
+}
+
+/// color of the haze, the mean of the pixels with the brightest dark channel
+pub fn atmospheric_light(pixels: &[[f32; 3]], dark: &[f32]) -> [f32; 3] {
+    let mut order: Vec<usize> = (0..pixels.len()).collect();
+    let count = ((pixels.len() as f32 * BRIGHTEST) as usize).max(1);
+    order.select_nth_unstable_by(count - 1, |a, b| dark[*b].total_cmp(&dark[*a]));
+
+    let mut light = [0f32; 3];
+    for i in &order[..count] {
+        for c in 0..3 {
+            light[c] += pixels[*i][c] / count as f32;
+        }
+    }
+    light
+}
+
+/// removes (positive `strength`, up to 1) or adds (negative `strength`, down to -1) haze to an
+/// image stored row by row. The transmission of the haze is estimated with the dark channel prior
+/// and refined with a guided filter driven by the luminance of the image
+pub fn dehaze(pixels: &mut [[f32; 3]], width: usize, height: usize, strength: f32) {
+    assert_eq!(pixels.len(), width * height);
+    if strength == 0.0 || pixels.is_empty() {
+        return;
+    }
+
+    let radius = ((PATCH_RADIUS * width.max(height) as f32) as usize).max(1);
+    let light = atmospheric_light(
+        pixels,
+        &dark_channel(pixels, width, height, [1.0; 3], radius),
+    );
+
+    let haze: Vec<f32> = dark_channel(pixels, width, height, light, radius)
+        .into_iter()
+        .map(|d| OMEGA * d)
+        .collect();
+    let luma: Vec<f32> = pixels
+        .iter()
+        .map(|p| 0.2126 * p[0] + 0.7152 * p[1] + 0.0722 * p[2])
+        .collect();
+    let haze = guided_filter(&luma, &haze, width, height, 4 * radius, REFINE_EPS);
+
+    let amount = strength.clamp(-1.0, 1.0);
+    for (pixel, h) in pixels.iter_mut().zip(haze) {
+        // the transmission is 1 - haze, the strength scales the haze
+        let transmission = 1.0 - amount.abs() * h.clamp(0.0, 1.0);
+        let scale = if amount > 0.0 {
+            1.0 / transmission.max(MIN_TRANSMISSION)
+        } else {
+            transmission
+        };
+        for c in 0..3 {
+            pixel[c] = light[c] + (pixel[c] - light[c]) * scale;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::linear_gradient_more_points;
+    use image::Rgba;
+
+    const SIZE: usize = 64;
+    const LIGHT: [f32; 3] = [0.85, 0.85, 0.9];
+
+    // a clear scene where every patch has a dark channel
+    fn scene() -> Vec<[f32; 3]> {
+        let gradient = linear_gradient_more_points(
+            (SIZE as u32, SIZE as u32),
+            vec![
+                (Rgba([200u8, 40, 0, 255]), 0.0),
+                (Rgba([0u8, 160, 60, 255]), 50.0),
+                (Rgba([30u8, 0, 180, 255]), 100.0),
+            ],
+            0.0,
+        );
+        gradient
+            .pixels()
+            .map(|p| std::array::from_fn(|c| p[c] as f32 / 255.0))
+            .collect()
+    }
+
+    // haze that gets denser towards the top of the image, with a few pixels of sky
+    fn hazy(scene: &[[f32; 3]]) -> Vec<[f32; 3]> {
+        scene
+            .iter()
+            .enumerate()
+            .map(|(i, p)| {
+                let y = i / SIZE;
+                if y == 0 {
+                    return LIGHT;
+                }
+                let t = 0.3 + 0.6 * y as f32 / SIZE as f32;
+                std::array::from_fn(|c| p[c] * t + LIGHT[c] * (1.0 - t))
+            })
+            .collect()
+    }
+
+    fn error(a: &[[f32; 3]], b: &[[f32; 3]]) -> f32 {
+        // the first row is the sky
+        a.iter()
+            .zip(b)
+            .skip(SIZE)
+            .map(|(p, q)| (0..3).map(|c| (p[c] - q[c]).abs()).sum::<f32>())
+            .sum::<f32>()
+            / a.len() as f32
+    }
+
+    #[test]
+    fn zero_strength_does_nothing() {
+        let mut pixels = hazy(&scene());
+        dehaze(&mut pixels, SIZE, SIZE, 0.0);
+        assert_eq!(pixels, hazy(&scene()));
+    }
+
+    #[test]
+    fn min_filter_takes_the_minimum_of_the_window() {
+        let mut data: Vec<f32> = (0..25).map(|i| if i == 12 { 0.0 } else { 1.0 }).collect();
+        min_filter(&mut data, 5, 5, 1);
+        let zeros: Vec<usize> = (0..25).filter(|i| data[*i] == 0.0).collect();
+        assert_eq!(zeros, vec![6, 7, 8, 11, 12, 13, 16, 17, 18]);
+    }
+
+    #[test]
+    fn estimates_the_atmospheric_light() {
+        let pixels = hazy(&scene());
+        let dark = dark_channel(&pixels, SIZE, SIZE, [1.0; 3], 1);
+        let light = atmospheric_light(&pixels, &dark);
+        for c in 0..3 {
+            assert!((light[c] - LIGHT[c]).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn removes_the_haze() {
+        let (clear, mut pixels) = (scene(), hazy(&scene()));
+        let before = error(&pixels, &clear);
+        dehaze(&mut pixels, SIZE, SIZE, 1.0);
+        assert!(error(&pixels, &clear) < before / 2.0);
+    }
+
+    #[test]
+    fn negative_strength_adds_haze() {
+        let (clear, mut pixels) = (scene(), hazy(&scene()));
+        let before = error(&pixels, &clear);
+        dehaze(&mut pixels, SIZE, SIZE, -1.0);
+        assert!(error(&pixels, &clear) > before);
+    }
+}
@@ -0,0 +1,170 @@
+use rayon::prelude::*;
+
+// side of a grain with size 0 relative to the longest side of the image,
+// a size of 1 makes the grains four times bigger
+pub const GRAIN_SCALE: f32 = 1.0 / 3000.0;
+
+// the finer octaves are added with a weight of roughness^octave
+const OCTAVES: usize = 3;
+
+// standard deviation of the lightness noise with amount 1
+const STRENGTH: f32 = 0.08;
+
+// uniform value in -1..1 of the lattice point (x, y)
+fn hash(x: i32, y: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (y as u32).wrapping_mul(0xd816_3841)
+        ^ seed.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x7feb_352d);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x846c_a68b);
+    h ^= h >> 16;
+    h as f32 / u32::MAX as f32 * 2.0 - 1.0
+}
+
+// smoothly interpolated value noise, `x` and `y` are in grains
+fn value_noise(x: f32, y: f32, seed: u32) -> f32 {
+    let (fx, fy) = (x.floor(), y.floor());
+    let (ix, iy) = (fx as i32, fy as i32);
+    let (tx, ty) = (x - fx, y - fy);
+    let (sx, sy) = (tx * tx * (3.0 - 2.0 * tx), ty * ty * (3.0 - 2.0 * ty));
+
+    let top = hash(ix, iy, seed) + (hash(ix + 1, iy, seed) - hash(ix, iy, seed)) * sx;
+    let bottom =
+        hash(ix, iy + 1, seed) + (hash(ix + 1, iy + 1, seed) - hash(ix, iy + 1, seed)) * sx;
+    top + (bottom - top) * sy
+}
+
+/// adds film grain to a lightness channel stored row by row.
+/// `amount` goes from 0 to 1, `size` from 0 (fine) to 1 (coarse) and `roughness` from 0 (soft)
+/// to 1 (harsh). The grain is defined relative to the image, so a downscaled render has the same
+/// pattern averaged over its bigger pixels, and the same `seed` always gives the same grain
+pub fn add_grain(
+    lightness: &mut [f32],
+    width: usize,
+    height: usize,
+    amount: f32,
+    size: f32,
+    roughness: f32,
+    seed: u32,
+) {
+    assert_eq!(lightness.len(), width * height);
+    if amount <= 0.0 || width == 0 || height == 0 {
+        return;
+    }
+
+    let side = width.max(height) as f32;
+    // side of a grain in pixels
+    let grain = GRAIN_SCALE * (1.0 + 3.0 * size.clamp(0.0, 1.0)) * side;
+
+    let weights: Vec<f32> = (0..OCTAVES)
+        .map(|octave| roughness.clamp(0.0, 1.0).powi(octave as i32))
+        .collect();
+    let norm = weights.iter().map(|w| w * w).sum::<f32>().sqrt();
+    let octaves: Vec<(f32, f32)> = weights
+        .iter()
+        .enumerate()
+        .map(|(octave, weight)| {
+            let frequency = (1 << octave) as f32;
+            // a pixel bigger than a grain averages many of them, so the noise gets weaker
+            let average = (grain / frequency).min(1.0);
+            (frequency, weight * average / norm)
+        })
+        .collect();
+
+    lightness
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let v = (y as f32 + 0.5) / grain;
+            for (x, l) in row.iter_mut().enumerate() {
+                let u = (x as f32 + 0.5) / grain;
+                let noise: f32 = octaves
+                    .iter()
+                    .enumerate()
+                    .map(|(octave, (frequency, weight))| {
+                        weight
+                            * value_noise(
+                                u * frequency,
+                                v * frequency,
+                                seed.wrapping_add(octave as u32),
+                            )
+                    })
+                    .sum();
+                // like on film the grain is more visible in the midtones
+                let midtones = 1.0 - (2.0 * *l - 1.0).clamp(-1.0, 1.0).powi(2);
+                *l += amount * STRENGTH * noise * (0.25 + 0.75 * midtones);
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(size: usize) -> Vec<f32> {
+        vec![0.5; size * size]
+    }
+
+    fn mean_and_deviation(data: &[f32]) -> (f32, f32) {
+        let mean = data.iter().sum::<f32>() / data.len() as f32;
+        let variance = data.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / data.len() as f32;
+        (mean, variance.sqrt())
+    }
+
+    // averages blocks of `factor` x `factor` pixels
+    fn downscale(data: &[f32], size: usize, factor: usize) -> Vec<f32> {
+        let small = size / factor;
+        (0..small * small)
+            .map(|i| {
+                let (x, y) = (i % small * factor, i / small * factor);
+                let sum: f32 = (0..factor * factor)
+                    .map(|j| data[(y + j / factor) * size + x + j % factor])
+                    .sum();
+                sum / (factor * factor) as f32
+            })
+            .collect()
+    }
+
+    #[test]
+    fn zero_amount_does_nothing() {
+        let mut data = gray(64);
+        add_grain(&mut data, 64, 64, 0.0, 0.5, 0.5, 1);
+        assert_eq!(data, gray(64));
+    }
+
+    #[test]
+    fn the_seed_makes_the_grain_deterministic() {
+        let render = |seed| {
+            let mut data = gray(64);
+            add_grain(&mut data, 64, 64, 1.0, 0.5, 0.5, seed);
+            data
+        };
+        assert_eq!(render(7), render(7));
+        assert_ne!(render(7), render(8));
+    }
+
+    #[test]
+    fn grain_keeps_the_mean_lightness() {
+        let mut data = gray(256);
+        add_grain(&mut data, 256, 256, 1.0, 1.0, 0.5, 3);
+        let (mean, deviation) = mean_and_deviation(&data);
+        assert!((mean - 0.5).abs() < 0.01);
+        assert!(deviation > 0.01);
+    }
+
+    #[test]
+    fn preview_matches_the_downscaled_export() {
+        for (size, roughness) in [(0.0, 0.0), (1.0, 0.5), (1.0, 1.0)] {
+            let mut full = gray(1024);
+            add_grain(&mut full, 1024, 1024, 1.0, size, roughness, 5);
+            let mut preview = gray(256);
+            add_grain(&mut preview, 256, 256, 1.0, size, roughness, 5);
+
+            let expected = mean_and_deviation(&downscale(&full, 1024, 4)).1;
+            let deviation = mean_and_deviation(&preview).1;
+            assert!((deviation / expected - 1.0).abs() < 0.25);
+        }
+    }
+}
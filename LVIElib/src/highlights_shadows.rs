@@ -0,0 +1,98 @@
+use crate::blurs::gaussianblur::gaussian_blur_f32;
+
+// radius of the base layer relative to the longest side of the image,
+// this way preview and export see the same local contrast
+pub const BASE_SIGMA: f32 = 0.015;
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// recovers or boosts highlights and shadows of a lightness channel stored row by row,
+/// `highlights` and `shadows` go from -1 to 1.
+/// Only the blurred base layer is changed, so the local details are preserved
+pub fn highlights_shadows(
+    lightness: &mut [f32],
+    width: usize,
+    height: usize,
+    highlights: f32,
+    shadows: f32,
+) {
+    assert_eq!(lightness.len(), width * height);
+    if highlights == 0.0 && shadows == 0.0 {
+        return;
+    }
+
+    let sigma = BASE_SIGMA * width.max(height) as f32;
+    let base = gaussian_blur_f32(lightness, width, height, sigma);
+
+    for (l, b) in lightness.iter_mut().zip(base) {
+        let detail = *l - b;
+
+        // midtones are left untouched
+        let wh = smoothstep(0.5, 1.0, b);
+        let ws = 1.0 - smoothstep(0.0, 0.5, b);
+
+        // negative values compress towards the midtones, positive ones expand towards the extremes
+        let mut new_base = b;
+        new_base += highlights * wh * 0.5 * if highlights > 0.0 { 1.0 - b } else { b - 0.5 };
+        new_base += shadows * ws * 0.5 * if shadows > 0.0 { 0.5 - b } else { b };
+
+        *l = new_base + detail;
+    }
+}
+
+/// moves the white and the black point of a lightness value,
+/// `whites` and `blacks` go from -1 to 1 and the curve stays monotonic
+pub fn whites_blacks(lightness: f32, whites: f32, blacks: f32) -> f32 {
+    let ww = ((lightness - 0.5) / 0.5).clamp(0.0, 1.0).powi(2);
+    let wb = ((0.5 - lightness) / 0.5).clamp(0.0, 1.0).powi(2);
+    lightness + 0.25 * (whites * ww + blacks * wb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn neutral_parameters_do_nothing() {
+        let mut l: Vec<f32> = (0..64 * 64).map(|i| (i % 64) as f32 / 63.0).collect();
+        let original = l.clone();
+        highlights_shadows(&mut l, 64, 64, 0.0, 0.0);
+        assert_eq!(l, original);
+        assert_eq!(whites_blacks(0.3, 0.0, 0.0), 0.3);
+    }
+
+    #[test]
+    fn recovers_highlights_and_lifts_shadows() {
+        // left half dark, right half bright
+        let mut l: Vec<f32> = (0..64 * 64)
+            .map(|i| if i % 64 < 32 { 0.1 } else { 0.95 })
+            .collect();
+        highlights_shadows(&mut l, 64, 64, -1.0, 1.0);
+
+        assert!(l[32 * 64 + 2] > 0.1);
+        assert!(l[32 * 64 + 61] < 0.95);
+    }
+
+    #[test]
+    fn midtones_are_preserved() {
+        let mut l = vec![0.5f32; 32 * 32];
+        highlights_shadows(&mut l, 32, 32, -1.0, 1.0);
+        assert!(l.iter().all(|v| (v - 0.5).abs() < 1e-5));
+        assert!((whites_blacks(0.5, 1.0, -1.0) - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn whites_blacks_is_monotonic() {
+        for (w, b) in [(-1.0, -1.0), (1.0, 1.0), (-1.0, 1.0), (1.0, -1.0)] {
+            let mut last = f32::MIN;
+            for i in 0..=100 {
+                let v = whites_blacks(i as f32 / 100.0, w, b);
+                assert!(v >= last);
+                last = v;
+            }
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::oklch::Oklcha;
+
+pub const HUE_BANDS: usize = 8;
+
+// OkLch hue of the center of every band:
+// red, orange, yellow, green, aqua, blue, purple and magenta
+pub const BAND_CENTERS: [f32; HUE_BANDS] = [29.2, 53.4, 109.8, 142.5, 194.8, 264.1, 294.3, 328.4];
+
+// hue rotation in degrees when the hue parameter of a band is 1
+pub const MAX_HUE_SHIFT: f32 = 30.0;
+
+/// returns how much each band affects a color with the given hue (in degrees),
+/// the weights always sum to 1 and every hue belongs at most to two adjacent bands
+pub fn band_weights(hue: f32) -> [f32; HUE_BANDS] {
+    let hue = hue.rem_euclid(360.0);
+    let mut weights = [0f32; HUE_BANDS];
+
+    // the last band is followed by the first one of the next turn
+    let i = BAND_CENTERS
+        .iter()
+        .rposition(|c| *c <= hue)
+        .unwrap_or(HUE_BANDS - 1);
+    let next = (i + 1) % HUE_BANDS;
+
+    let width = (BAND_CENTERS[next] - BAND_CENTERS[i]).rem_euclid(360.0);
+    let t = (hue - BAND_CENTERS[i]).rem_euclid(360.0) / width;
+
+    // smoothstep avoids visible edges where a band ends
+    let s = t * t * (3.0 - 2.0 * t);
+    weights[i] = 1.0 - s;
+    weights[next] += s;
+    weights
+}
+
+/// adjusts the color depending on its hue,
+/// `parameters` holds hue shift, saturation and luminance of every band, all from -1 to 1
+pub fn mix_hsl(pixel: &mut Oklcha, parameters: &[f32]) {
+    assert_eq!(parameters.len(), 3 * HUE_BANDS);
+
+    let weights = band_weights(*pixel.h());
+    let (mut shift, mut saturation, mut luminance) = (0f32, 0f32, 0f32);
+    for (w, band) in weights.iter().zip(parameters.chunks(3)) {
+        shift += w * band[0];
+        saturation += w * band[1];
+        luminance += w * band[2];
+    }
+
+    // grays have no hue, the luminance change fades out together with the chroma
+    let colorfulness = (*pixel.c() / 0.1).min(1.0);
+
+    *pixel.h_mut() = (*pixel.h() + shift * MAX_HUE_SHIFT).rem_euclid(360.0);
+    *pixel.l_mut() *= 2f32.powf(luminance * colorfulness);
+    *pixel.c_mut() *= (1.0 + saturation).max(0.0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_sum_to_one() {
+        for h in 0..720 {
+            let w = band_weights(h as f32 / 2.0);
+            assert!((w.iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn band_centers_are_not_blended() {
+        for (i, c) in BAND_CENTERS.iter().enumerate() {
+            assert!((band_weights(*c)[i] - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn only_the_selected_band_changes() {
+        let mut parameters = [0f32; 3 * HUE_BANDS];
+        // desaturate greens
+        parameters[3 * 3 + 1] = -1.0;
+
+        let mut green = Oklcha::new(0.8, 0.25, BAND_CENTERS[3], 1.0);
+        let mut blue = Oklcha::new(0.45, 0.3, BAND_CENTERS[5], 1.0);
+        mix_hsl(&mut green, &parameters);
+        mix_hsl(&mut blue, &parameters);
+
+        assert_eq!(*green.c(), 0.0);
+        assert_eq!(*blue.c(), 0.3);
+    }
+}
@@ -1,33 +1,319 @@
-use crate::matrix::Matrix;
-
-pub fn homography<T: Clone>(mat: Matrix<f32>, img: &mut Matrix<T>, filler: T) {
-    let mut output: Vec<T> = Vec::new();
-
-    for i in 0..img.get_content().len() {
-        let mut pos = Matrix::new(
-            vec![
-                (i % img.width()/*- img.width() / 2*/) as f32,
-                (i / img.width()/*- img.height() / 2*/) as f32,
-                1f32,
-            ],
-            3,
-            1,
-        );
-
-        // INEFFICIENT, should keep in memory the values for same x-s and y-s
-        pos = (mat.clone() * pos).unwrap();
-        let v = pos.get_content();
-
-        output.push(
-            match img.get_element(
-                ((v[0] / v[2]).round()/*+ (img.width() / 2) as f32*/) as usize,
-                ((v[1] / v[2]).round()/*+ (img.height() / 2) as f32*/) as usize,
-            ) {
-                Ok(val) => val,
-                Err(_) => filler.clone(),
-            },
-        )
-    }
-
-    img.update_content(output).unwrap();
-}
+use image::{Pixel, Primitive};
+use num_traits::NumCast;
+use rayon::prelude::*;
+
+use nalgebra::{SMatrix, SVector};
+
+use crate::blurs::boxblur::CRgbaImage;
+
+// resampling used when the pixels of the output fall between the pixels of the input
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Interpolation {
+    #[default]
+    Bilinear,
+    Bicubic,
+}
+
+/// warps an image through a 3x3 homography stored row by row, the output has the size of the input.
+/// The matrix maps the points of the output, in pixels with the origin in the top left corner,
+/// to the points of the input; the points out of the input are transparent
+pub fn homography<P>(
+    img: &CRgbaImage<P>,
+    matrix: &[f32; 9],
+    interpolation: Interpolation,
+) -> CRgbaImage<P>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Primitive + Send + Sync,
+{
+    let m = matrix;
+    warp(img, img.width(), img.height(), interpolation, |x, y| {
+        let w = m[6] * x + m[7] * y + m[8];
+        if w.abs() < 1e-9 {
+            // the point goes to infinity
+            return (-1.0, -1.0);
+        }
+        (
+            (m[0] * x + m[1] * y + m[2]) / w,
+            (m[3] * x + m[4] * y + m[5]) / w,
+        )
+    })
+}
+
+/// the homography, stored row by row, that maps the 4 points of `from` to the 4 points of `to`.
+/// Returns `None` if three of the points are aligned
+pub fn perspective_matrix(from: [[f32; 2]; 4], to: [[f32; 2]; 4]) -> Option<[f32; 9]> {
+    // h8 is fixed to 1, every pair of points gives two equations in the other 8 entries
+    let mut a = SMatrix::<f64, 8, 8>::zeros();
+    let mut b = SVector::<f64, 8>::zeros();
+    for (i, (p, q)) in from.iter().zip(&to).enumerate() {
+        let (x, y) = (p[0] as f64, p[1] as f64);
+        let (u, v) = (q[0] as f64, q[1] as f64);
+        a.set_row(
+            2 * i,
+            &SMatrix::<f64, 1, 8>::from_row_slice(&[x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y]),
+        );
+        a.set_row(
+            2 * i + 1,
+            &SMatrix::<f64, 1, 8>::from_row_slice(&[0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y]),
+        );
+        b[2 * i] = u;
+        b[2 * i + 1] = v;
+    }
+
+    let h = a.lu().solve(&b)?;
+    if h.iter().any(|v| !v.is_finite()) {
+        return None;
+    }
+    let mut out = [1f32; 9];
+    for (o, v) in out.iter_mut().zip(h.iter()) {
+        *o = *v as f32;
+    }
+    Some(out)
+}
+
+// catmull-rom weights of the 4 pixels around a point at distance `t` from the second one
+fn cubic_weights(t: f32) -> [f32; 4] {
+    let (t2, t3) = (t * t, t * t * t);
+    [
+        0.5 * (-t3 + 2.0 * t2 - t),
+        0.5 * (3.0 * t3 - 5.0 * t2 + 2.0),
+        0.5 * (-3.0 * t3 + 4.0 * t2 + t),
+        0.5 * (t3 - t2),
+    ]
+}
+
+// writes in `out` the channels of the image at (x, y), where the pixel (i, j) is centered in (i, j),
+// the pixels out of the image are replaced by the closest ones on the border
+fn sample<S: Primitive>(
+    src: &[S],
+    (width, height): (usize, usize),
+    channels: usize,
+    (x, y): (f32, f32),
+    interpolation: Interpolation,
+    out: &mut [f32],
+) {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (tx, ty) = (x - x0, y - y0);
+    let (x0, y0) = (x0 as isize, y0 as isize);
+
+    let (wx, wy, taps): ([f32; 4], [f32; 4], std::ops::Range<isize>) = match interpolation {
+        Interpolation::Bilinear => ([1.0 - tx, tx, 0.0, 0.0], [1.0 - ty, ty, 0.0, 0.0], 0..2),
+        Interpolation::Bicubic => (cubic_weights(tx), cubic_weights(ty), -1..3),
+    };
+    let first = taps.start;
+
+    out.iter_mut().for_each(|v| *v = 0.0);
+    for j in taps.clone() {
+        let row = (y0 + j).clamp(0, height as isize - 1) as usize * width;
+        let wj = wy[(j - first) as usize];
+        for i in taps.clone() {
+            let col = (x0 + i).clamp(0, width as isize - 1) as usize;
+            let w = wj * wx[(i - first) as usize];
+            let p = &src[(row + col) * channels..(row + col + 1) * channels];
+            for (v, c) in out.iter_mut().zip(p) {
+                *v += w * <f32 as NumCast>::from(*c).unwrap();
+            }
+        }
+    }
+}
+
+/// resamples an image into a new one of size `width` x `height`.
+/// `map` takes the coordinates of a point of the output, in pixels with the origin in the top left
+/// corner, and returns the point of the input it comes from; the points out of the input are transparent
+pub fn warp<P, F>(
+    img: &CRgbaImage<P>,
+    width: u32,
+    height: u32,
+    interpolation: Interpolation,
+    map: F,
+) -> CRgbaImage<P>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Primitive + Send + Sync,
+    F: Fn(f32, f32) -> (f32, f32) + Sync,
+{
+    let channels = P::CHANNEL_COUNT as usize;
+    let mut out = vec![P::Subpixel::DEFAULT_MIN_VALUE; width as usize * height as usize * channels];
+    let (iw, ih) = img.dimensions();
+    if out.is_empty() || iw == 0 || ih == 0 {
+        return CRgbaImage::<P>::from_raw(width, height, out).unwrap();
+    }
+
+    let max = <f32 as NumCast>::from(P::Subpixel::DEFAULT_MAX_VALUE).unwrap();
+    // integer subpixels are rounded, the float ones go from 0 to 1
+    let integer = max > 1.0;
+    let src = img.as_raw();
+
+    out.par_chunks_mut(width as usize * channels)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let mut value = vec![0f32; channels];
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                let (sx, sy) = map(x as f32 + 0.5, y as f32 + 0.5);
+                if !(0.0..=iw as f32).contains(&sx) || !(0.0..=ih as f32).contains(&sy) {
+                    continue;
+                }
+
+                sample(
+                    src,
+                    (iw as usize, ih as usize),
+                    channels,
+                    (sx - 0.5, sy - 0.5),
+                    interpolation,
+                    &mut value,
+                );
+                for (c, v) in pixel.iter_mut().zip(&value) {
+                    let v = v.clamp(0.0, max);
+                    *c = NumCast::from(if integer { v.round() } else { v }).unwrap();
+                }
+            }
+        });
+
+    CRgbaImage::<P>::from_raw(width, height, out).unwrap()
+}
+
+/// size of the largest rectangle with horizontal sides inside a `width` x `height` rectangle
+/// rotated by `angle` degrees around its center
+pub fn largest_rotated_rect(width: f32, height: f32, angle: f32) -> (f32, f32) {
+    if width <= 0.0 || height <= 0.0 {
+        return (0.0, 0.0);
+    }
+    let (sin, cos) = (
+        angle.to_radians().sin().abs(),
+        angle.to_radians().cos().abs(),
+    );
+    let (long, short) = (width.max(height), width.min(height));
+
+    if short <= 2.0 * sin * cos * long || (sin - cos).abs() < 1e-6 {
+        // the rectangle touches the long sides only, two of its corners are on them
+        let half = 0.5 * short;
+        let (w, h) = if width >= height {
+            (half / sin.max(1e-6), half / cos)
+        } else {
+            (half / cos, half / sin.max(1e-6))
+        };
+        (w.min(width), h.min(height))
+    } else {
+        let cos_2a = cos * cos - sin * sin;
+        (
+            (width * cos - height * sin) / cos_2a,
+            (height * cos - width * sin) / cos_2a,
+        )
+    }
+}
+
+/// rotates an image by `angle` degrees counterclockwise around its center.
+/// With `crop` the output is the largest rectangle without empty corners,
+/// otherwise it has the size of the input and the corners left empty are transparent
+pub fn rotate<P>(
+    img: &CRgbaImage<P>,
+    angle: f32,
+    interpolation: Interpolation,
+    crop: bool,
+) -> CRgbaImage<P>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Primitive + Send + Sync,
+{
+    let (iw, ih) = (img.width() as f32, img.height() as f32);
+    let (width, height) = if crop {
+        let (w, h) = largest_rotated_rect(iw, ih, angle);
+        ((w.floor() as u32).max(1), (h.floor() as u32).max(1))
+    } else {
+        img.dimensions()
+    };
+
+    // every point of the output is turned back clockwise into the input, y points down
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let (ocx, ocy) = (width as f32 / 2.0, height as f32 / 2.0);
+    warp(img, width, height, interpolation, |x, y| {
+        let (dx, dy) = (x - ocx, y - ocy);
+        (
+            iw / 2.0 + dx * cos - dy * sin,
+            ih / 2.0 + dx * sin + dy * cos,
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn gradient() -> RgbaImage {
+        RgbaImage::from_fn(16, 16, |x, y| {
+            Rgba([(x * 16) as u8, (y * 16) as u8, 128, 255])
+        })
+    }
+
+    #[test]
+    fn no_rotation_keeps_the_image() {
+        let img = gradient();
+        assert_eq!(rotate(&img, 0.0, Interpolation::Bilinear, false), img);
+        assert_eq!(rotate(&img, 0.0, Interpolation::Bicubic, true), img);
+    }
+
+    #[test]
+    fn quarter_turn_is_exact() {
+        let img = gradient();
+        let turned = rotate(&img, 90.0, Interpolation::Bicubic, false);
+        assert_eq!(turned, image::imageops::rotate270(&img));
+    }
+
+    #[test]
+    fn largest_rectangle_of_a_rotated_square() {
+        let (w, h) = largest_rotated_rect(100.0, 100.0, 45.0);
+        assert!((w - 100.0 / 2f32.sqrt()).abs() < 1e-3 && (h - w).abs() < 1e-3);
+
+        let (w, h) = largest_rotated_rect(300.0, 200.0, 0.0);
+        assert_eq!((w, h), (300.0, 200.0));
+    }
+
+    #[test]
+    fn perspective_matrix_maps_the_corners() {
+        let square = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+        let quad = [[0.2, 0.1], [0.9, 0.0], [1.0, 1.0], [0.0, 0.8]];
+        let m = perspective_matrix(square, quad).unwrap();
+        for (p, q) in square.iter().zip(&quad) {
+            let w = m[6] * p[0] + m[7] * p[1] + m[8];
+            let x = (m[0] * p[0] + m[1] * p[1] + m[2]) / w;
+            let y = (m[3] * p[0] + m[4] * p[1] + m[5]) / w;
+            assert!((x - q[0]).abs() < 1e-5 && (y - q[1]).abs() < 1e-5);
+        }
+
+        // aligned points
+        let line = [[0.0, 0.0], [1.0, 1.0], [2.0, 2.0], [3.0, 3.0]];
+        assert!(perspective_matrix(square, line).is_none());
+    }
+
+    #[test]
+    fn identity_homography_keeps_the_image() {
+        let img = gradient();
+        let identity = [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        assert_eq!(homography(&img, &identity, Interpolation::Bilinear), img);
+    }
+
+    #[test]
+    fn homography_stretches_the_selected_area() {
+        // the left half of the image fills the whole output
+        let img = gradient();
+        let half = [0.5, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0];
+        let out = homography(&img, &half, Interpolation::Bilinear);
+        assert_eq!(out.get_pixel(0, 3), img.get_pixel(0, 3));
+        assert_eq!(out.get_pixel(3, 3)[0], 20);
+        assert_eq!(out.get_pixel(15, 3)[0], 116);
+    }
+
+    #[test]
+    fn auto_crop_leaves_no_empty_corners() {
+        let img = gradient();
+        let rotated = rotate(&img, 10.0, Interpolation::Bilinear, true);
+        assert!(rotated.width() < 16 && rotated.height() < 16);
+        assert!(rotated.pixels().all(|p| p[3] == 255));
+
+        // without the crop the corners are transparent
+        let rotated = rotate(&img, 10.0, Interpolation::Bilinear, false);
+        assert_eq!(rotated.get_pixel(0, 0)[3], 0);
+    }
+}
@@ -0,0 +1,220 @@
+use image::{Pixel, Primitive};
+use num_traits::NumCast;
+use rayon::prelude::*;
+
+use crate::blurs::boxblur::CRgbaImage;
+use crate::image_geometry::{warp, Interpolation};
+
+// gamma of the encoded images, the same used by `linear_srgb`
+const GAMMA: f32 = 2.2;
+
+/// polynomial model of a lens. `r` is the distance from the center of the image
+/// divided by the half diagonal, so it is 1 in the corners.
+/// A point of the corrected image at radius `r` comes from the radius
+/// `r (1 + k1 r^2 + k2 r^4 + k3 r^6)` of the input, the linear light is multiplied by
+/// `1 + v1 r^2 + v2 r^4 + v3 r^6` and the red and the blue channels are scaled around the center
+/// by the two factors of `chromatic_aberration` to be aligned with the green one
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LensModel {
+    pub distortion: [f32; 3],
+    pub vignetting: [f32; 3],
+    pub chromatic_aberration: [f32; 2],
+}
+
+impl Default for LensModel {
+    fn default() -> Self {
+        LensModel {
+            distortion: [0.0; 3],
+            vignetting: [0.0; 3],
+            chromatic_aberration: [1.0; 2],
+        }
+    }
+}
+
+// k1 r^2 + k2 r^4 + k3 r^6
+fn polynomial(k: &[f32; 3], r2: f32) -> f32 {
+    r2 * (k[0] + r2 * (k[1] + r2 * k[2]))
+}
+
+impl LensModel {
+    pub fn is_identity(&self) -> bool {
+        *self == LensModel::default()
+    }
+
+    /// the gain of the linear light at the squared radius `r2`
+    pub fn vignetting_gain(&self, r2: f32) -> f32 {
+        (1.0 + polynomial(&self.vignetting, r2)).max(0.0)
+    }
+
+    /// the scale from the center of the point of the input at the squared radius `r2`
+    pub fn distortion_factor(&self, r2: f32) -> f32 {
+        1.0 + polynomial(&self.distortion, r2)
+    }
+}
+
+/// multiplies the linear light of the color channels by the vignetting gain of the lens
+pub fn correct_vignetting<P>(img: &mut CRgbaImage<P>, lens: &LensModel)
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Primitive + Send + Sync,
+{
+    let (width, height) = img.dimensions();
+    if lens.vignetting == [0.0; 3] || width == 0 || height == 0 {
+        return;
+    }
+
+    let channels = P::CHANNEL_COUNT as usize;
+    // the alpha channel is left unchanged
+    let colors = channels.min(3);
+    let max = <f32 as NumCast>::from(P::Subpixel::DEFAULT_MAX_VALUE).unwrap();
+    let integer = max > 1.0;
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let half_diagonal2 = cx * cx + cy * cy;
+
+    img.par_chunks_mut(width as usize * channels)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let dy = y as f32 + 0.5 - cy;
+            for (x, pixel) in row.chunks_exact_mut(channels).enumerate() {
+                let dx = x as f32 + 0.5 - cx;
+                // with a power law the gain of the linear light becomes a gain of the encoded values
+                let gain = lens
+                    .vignetting_gain((dx * dx + dy * dy) / half_diagonal2)
+                    .powf(1.0 / GAMMA);
+                for c in &mut pixel[..colors] {
+                    let v = (<f32 as NumCast>::from(*c).unwrap() * gain).clamp(0.0, max);
+                    *c = NumCast::from(if integer { v.round() } else { v }).unwrap();
+                }
+            }
+        });
+}
+
+// resamples the image through the distortion of the lens, with every channel scaled by `scale`
+fn undistort<P>(
+    img: &CRgbaImage<P>,
+    lens: &LensModel,
+    scale: f32,
+    interpolation: Interpolation,
+) -> CRgbaImage<P>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Primitive + Send + Sync,
+{
+    let (width, height) = img.dimensions();
+    let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+    let half_diagonal2 = cx * cx + cy * cy;
+
+    warp(img, width, height, interpolation, |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        let k = scale * lens.distortion_factor((dx * dx + dy * dy) / half_diagonal2);
+        (cx + dx * k, cy + dy * k)
+    })
+}
+
+/// corrects the vignetting, the distortion and the lateral chromatic aberration of an image.
+/// The size of the image does not change, the points that come from out of the input are transparent
+pub fn correct_lens<P>(
+    img: &CRgbaImage<P>,
+    lens: &LensModel,
+    interpolation: Interpolation,
+) -> CRgbaImage<P>
+where
+    P: Pixel + Send + Sync,
+    P::Subpixel: Primitive + Send + Sync,
+{
+    // the vignetting depends on the position on the sensor, so it is corrected first
+    let mut img = img.clone();
+    correct_vignetting(&mut img, lens);
+    if lens.distortion == [0.0; 3] && lens.chromatic_aberration == [1.0; 2] {
+        return img;
+    }
+
+    let mut out = undistort(&img, lens, 1.0, interpolation);
+    if lens.chromatic_aberration == [1.0; 2] || P::CHANNEL_COUNT < 3 {
+        return out;
+    }
+
+    // red and blue are resampled on their own and copied over the green image
+    let channels = P::CHANNEL_COUNT as usize;
+    for (c, scale) in [
+        (0, lens.chromatic_aberration[0]),
+        (2, lens.chromatic_aberration[1]),
+    ] {
+        if scale == 1.0 {
+            continue;
+        }
+        let channel = undistort(&img, lens, scale, interpolation);
+        out.par_chunks_mut(channels)
+            .zip(channel.par_chunks(channels))
+            .for_each(|(o, s)| o[c] = s[c]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn gray() -> RgbaImage {
+        RgbaImage::from_pixel(32, 32, Rgba([100, 100, 100, 255]))
+    }
+
+    #[test]
+    fn identity_keeps_the_image() {
+        let img = RgbaImage::from_fn(16, 16, |x, y| {
+            Rgba([(x * 16) as u8, (y * 16) as u8, 7, 255])
+        });
+        let out = correct_lens(&img, &LensModel::default(), Interpolation::Bilinear);
+        assert_eq!(out, img);
+    }
+
+    #[test]
+    fn vignetting_brightens_the_corners() {
+        let lens = LensModel {
+            vignetting: [0.5, 0.0, 0.0],
+            ..Default::default()
+        };
+        let out = correct_lens(&gray(), &lens, Interpolation::Bilinear);
+        assert_eq!(out.get_pixel(16, 16)[0], 100);
+        assert!(out.get_pixel(0, 0)[0] > 110);
+        assert_eq!(out.get_pixel(0, 0)[3], 255);
+    }
+
+    #[test]
+    fn distortion_moves_the_points_from_the_center() {
+        // a vertical line at a quarter of the width
+        let img = RgbaImage::from_fn(64, 64, |x, _| {
+            let v = if x == 16 { 255 } else { 0 };
+            Rgba([v, v, v, 255])
+        });
+        let lens = LensModel {
+            distortion: [-0.5, 0.0, 0.0],
+            ..Default::default()
+        };
+        let out = correct_lens(&img, &lens, Interpolation::Bilinear);
+
+        // the points are taken closer to the center, so the line moves outwards
+        let brightest = (0..32).max_by_key(|&x| out.get_pixel(x, 32)[0]).unwrap();
+        assert!(brightest < 16);
+        assert_eq!(out.get_pixel(32, 32)[0], 0);
+    }
+
+    #[test]
+    fn chromatic_aberration_moves_red_and_blue_only() {
+        let img = RgbaImage::from_fn(64, 64, |x, _| {
+            let v = if x == 8 { 255 } else { 0 };
+            Rgba([v, v, v, 255])
+        });
+        let lens = LensModel {
+            chromatic_aberration: [0.98, 1.0],
+            ..Default::default()
+        };
+        let out = correct_lens(&img, &lens, Interpolation::Bilinear);
+
+        let (red, green) = (out.get_pixel(8, 32)[0], out.get_pixel(8, 32)[1]);
+        assert_eq!(green, 255);
+        assert!(red < 255);
+        assert_eq!(out.get_pixel(8, 32)[2], 255);
+    }
+}
@@ -1,7 +1,12 @@
 #![allow(non_snake_case)]
 
+pub mod auto_tone;
 pub mod blurs;
+pub mod channel_mixer;
+pub mod color_grading;
 pub mod contrast;
+pub mod dehaze;
+pub mod highlights_shadows;
 pub mod math;
 pub mod matrix;
 pub mod traits;
@@ -14,10 +19,18 @@ use rustfft::num_complex::Complex as _Complex;
 pub type Complex<T> = _Complex<T>;
 
 pub mod hsl;
+pub mod hsl_mixer;
+pub mod local_contrast;
 pub mod linear_srgb;
 pub mod oklab;
+pub mod oklch;
+pub mod range_mask;
+pub mod rasterize;
 
+pub mod grain;
 pub mod image_geometry;
+pub mod lens_correction;
 pub mod sharpening;
 pub mod spline;
+pub mod wavelet_denoise;
 pub mod white_balance;
@@ -0,0 +1,96 @@
+use crate::blurs::guidedfilter::guided_filter;
+
+// size of the smoothing window relative to the longest side of the image,
+// clarity works on large structures while texture works on fine details
+pub const CLARITY_RADIUS: f32 = 0.02;
+pub const TEXTURE_RADIUS: f32 = 0.003;
+
+// edges with a lightness variance above these values are preserved
+const CLARITY_EPS: f32 = 0.01;
+const TEXTURE_EPS: f32 = 0.002;
+
+/// splits the lightness into an edge-preserving base layer and a detail layer,
+/// then scales the details by `1 + amount`; the change fades out on the extremes to avoid clipping
+pub fn enhance_details(
+    lightness: &mut [f32],
+    width: usize,
+    height: usize,
+    amount: f32,
+    radius: usize,
+    eps: f32,
+) {
+    if amount == 0.0 {
+        return;
+    }
+
+    let base = guided_filter(lightness, lightness, width, height, radius, eps);
+    for (l, b) in lightness.iter_mut().zip(base) {
+        let midtones = 1.0 - (2.0 * b - 1.0).clamp(-1.0, 1.0).powi(2);
+        *l = b + (*l - b) * (1.0 + amount * midtones).max(0.0);
+    }
+}
+
+/// clarity and texture adjustments of a lightness channel stored row by row,
+/// both values go from -1 (smoother) to 1 (more local contrast)
+pub fn clarity_texture(
+    lightness: &mut [f32],
+    width: usize,
+    height: usize,
+    clarity: f32,
+    texture: f32,
+) {
+    assert_eq!(lightness.len(), width * height);
+    let size = width.max(height) as f32;
+
+    enhance_details(
+        lightness,
+        width,
+        height,
+        clarity,
+        ((CLARITY_RADIUS * size) as usize).max(1),
+        CLARITY_EPS,
+    );
+    enhance_details(
+        lightness,
+        width,
+        height,
+        texture,
+        ((TEXTURE_RADIUS * size) as usize).max(1),
+        TEXTURE_EPS,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pattern() -> Vec<f32> {
+        (0..64 * 64)
+            .map(|i| 0.5 + 0.05 * ((i % 64) as f32 / 3.0).sin())
+            .collect()
+    }
+
+    fn spread(data: &[f32]) -> f32 {
+        let max = data.iter().cloned().fold(f32::MIN, f32::max);
+        let min = data.iter().cloned().fold(f32::MAX, f32::min);
+        max - min
+    }
+
+    #[test]
+    fn zero_amount_does_nothing() {
+        let mut l = pattern();
+        clarity_texture(&mut l, 64, 64, 0.0, 0.0);
+        assert_eq!(l, pattern());
+    }
+
+    #[test]
+    fn positive_amount_increases_local_contrast() {
+        let mut more = pattern();
+        let mut less = pattern();
+        clarity_texture(&mut more, 64, 64, 1.0, 0.0);
+        clarity_texture(&mut less, 64, 64, -1.0, 0.0);
+
+        assert!(spread(&more) > spread(&pattern()));
+        assert!(spread(&less) < spread(&pattern()));
+    }
+}
@@ -0,0 +1,495 @@
+#![allow(dead_code)]
+
+use image::{ImageBuffer, Luma, LumaA, Pixel, Primitive, Rgb, Rgba};
+use std::ops::{Deref, DerefMut};
+
+use crate::oklab::{Oklab, Oklaba};
+use crate::traits::AsFloat;
+
+/// # OkLch Color Space:
+///
+/// OkLch is the polar form of the OkLab color space,
+/// its three values are represented following this scheme:
+///
+/// l: f32 -> the perceptual lightness, the same of OkLab, from 0.0 to 1.0
+/// c: f32 -> the chroma, the distance from the gray axis, from 0.0 to about 0.4 for sRGB colors
+/// h: f32 -> the hue angle from 0.0 to 360.0
+#[derive(PartialEq, Clone, Debug, Copy, Default)]
+#[repr(C)]
+#[allow(missing_docs)]
+pub struct Oklch {
+    channels: [f32; 3],
+}
+
+impl Oklch {
+    pub fn l(&self) -> &f32 {
+        &self.channels[0]
+    }
+
+    pub fn c(&self) -> &f32 {
+        &self.channels[1]
+    }
+
+    pub fn h(&self) -> &f32 {
+        &self.channels[2]
+    }
+
+    pub fn l_mut(&mut self) -> &mut f32 {
+        &mut self.channels[0]
+    }
+
+    pub fn c_mut(&mut self) -> &mut f32 {
+        &mut self.channels[1]
+    }
+
+    pub fn h_mut(&mut self) -> &mut f32 {
+        &mut self.channels[2]
+    }
+
+    pub fn new(l: f32, c: f32, h: f32) -> Oklch {
+        Oklch {
+            channels: [l, c, h],
+        }
+    }
+
+    pub fn from_components(oklch: [f32; 3]) -> Oklch {
+        Oklch { channels: oklch }
+    }
+}
+
+impl Pixel for Oklch {
+    type Subpixel = f32;
+
+    const CHANNEL_COUNT: u8 = 3;
+
+    #[inline(always)]
+    fn channels(&self) -> &[f32] {
+        &self.channels
+    }
+
+    #[inline(always)]
+    fn channels_mut(&mut self) -> &mut [f32] {
+        &mut self.channels
+    }
+
+    const COLOR_MODEL: &'static str = "OKLCH";
+
+    fn channels4(&self) -> (f32, f32, f32, f32) {
+        const CHANNELS: usize = 3;
+        let mut channels = [f32::MAX; 4];
+        channels[0..CHANNELS].copy_from_slice(&self.channels);
+        (channels[0], channels[1], channels[2], channels[3])
+    }
+
+    fn from_channels(a: f32, b: f32, c: f32, _d: f32) -> Oklch {
+        Oklch::new(a, b, c)
+    }
+
+    fn from_slice(slice: &[f32]) -> &Oklch {
+        assert_eq!(slice.len(), 3);
+        unsafe { &*(slice.as_ptr() as *const Oklch) }
+    }
+
+    fn from_slice_mut(slice: &mut [f32]) -> &mut Oklch {
+        assert_eq!(slice.len(), 3);
+        unsafe { &mut *(slice.as_mut_ptr() as *mut Oklch) }
+    }
+
+    fn to_rgb(&self) -> Rgb<f32> {
+        <Self as Into<Rgb<f32>>>::into(*self)
+    }
+
+    fn to_rgba(&self) -> Rgba<f32> {
+        self.to_rgb().to_rgba()
+    }
+
+    fn to_luma(&self) -> Luma<f32> {
+        Luma([*self.l()])
+    }
+
+    fn to_luma_alpha(&self) -> LumaA<f32> {
+        LumaA([*self.l(), 1.0])
+    }
+
+    fn map<F>(&self, f: F) -> Oklch
+    where
+        F: FnMut(f32) -> f32,
+    {
+        let mut this = *self;
+        this.apply(f);
+        this
+    }
+
+    fn apply<F>(&mut self, mut f: F)
+    where
+        F: FnMut(f32) -> f32,
+    {
+        for v in &mut self.channels {
+            *v = f(*v)
+        }
+    }
+
+    fn map_with_alpha<F, G>(&self, f: F, g: G) -> Oklch
+    where
+        F: FnMut(f32) -> f32,
+        G: FnMut(f32) -> f32,
+    {
+        let mut this = *self;
+        this.apply_with_alpha(f, g);
+        this
+    }
+
+    fn apply_with_alpha<F, G>(&mut self, f: F, _g: G)
+    where
+        F: FnMut(f32) -> f32,
+        G: FnMut(f32) -> f32,
+    {
+        // there is no alpha channel
+        self.apply(f);
+    }
+
+    fn map2<F>(&self, other: &Self, f: F) -> Oklch
+    where
+        F: FnMut(f32, f32) -> f32,
+    {
+        let mut this = *self;
+        this.apply2(other, f);
+        this
+    }
+
+    fn apply2<F>(&mut self, other: &Oklch, mut f: F)
+    where
+        F: FnMut(f32, f32) -> f32,
+    {
+        for (a, &b) in self.channels.iter_mut().zip(other.channels.iter()) {
+            *a = f(*a, b)
+        }
+    }
+
+    fn invert(&mut self) {
+        *self.h_mut() = (*self.h() + 180.0) % 360.0;
+    }
+
+    fn blend(&mut self, _other: &Oklch) {}
+}
+
+impl Deref for Oklch {
+    type Target = [f32; 3];
+    fn deref(&self) -> &Self::Target {
+        &self.channels
+    }
+}
+
+impl DerefMut for Oklch {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.channels
+    }
+}
+
+fn lab_to_lch(l: f32, a: f32, b: f32) -> [f32; 3] {
+    let c = a.hypot(b);
+    let h = b.atan2(a).to_degrees();
+    [l, c, if h < 0.0 { h + 360.0 } else { h }]
+}
+
+fn lch_to_lab(l: f32, c: f32, h: f32) -> [f32; 3] {
+    let (sin, cos) = h.to_radians().sin_cos();
+    [l, c * cos, c * sin]
+}
+
+impl From<Oklab> for Oklch {
+    fn from(value: Oklab) -> Self {
+        Oklch::from_components(lab_to_lch(*value.l(), *value.a(), *value.b()))
+    }
+}
+
+impl From<Oklch> for Oklab {
+    fn from(value: Oklch) -> Self {
+        Oklab::from_components(lch_to_lab(*value.l(), *value.c(), *value.h()))
+    }
+}
+
+impl From<Oklch> for Rgb<u8> {
+    fn from(value: Oklch) -> Rgb<u8> {
+        Rgb::from(Oklab::from(value))
+    }
+}
+
+impl From<Oklch> for Rgb<u16> {
+    fn from(value: Oklch) -> Rgb<u16> {
+        Rgb::from(Oklab::from(value))
+    }
+}
+
+impl From<Oklch> for Rgb<f32> {
+    fn from(value: Oklch) -> Rgb<f32> {
+        Rgb::from(Oklab::from(value))
+    }
+}
+
+impl<T: Primitive + AsFloat> From<Rgb<T>> for Oklch {
+    fn from(rgb: Rgb<T>) -> Self {
+        Oklch::from(Oklab::from(rgb))
+    }
+}
+
+pub type OklchImage = ImageBuffer<Oklch, Vec<f32>>;
+
+#[derive(PartialEq, Clone, Debug, Copy, Default)]
+#[repr(C)]
+#[allow(missing_docs)]
+pub struct Oklcha {
+    channels: [f32; 4],
+}
+
+impl Oklcha {
+    pub fn l(&self) -> &f32 {
+        &self.channels[0]
+    }
+
+    pub fn c(&self) -> &f32 {
+        &self.channels[1]
+    }
+
+    pub fn h(&self) -> &f32 {
+        &self.channels[2]
+    }
+
+    pub fn alpha(&self) -> &f32 {
+        &self.channels[3]
+    }
+
+    pub fn l_mut(&mut self) -> &mut f32 {
+        &mut self.channels[0]
+    }
+
+    pub fn c_mut(&mut self) -> &mut f32 {
+        &mut self.channels[1]
+    }
+
+    pub fn h_mut(&mut self) -> &mut f32 {
+        &mut self.channels[2]
+    }
+
+    pub fn alpha_mut(&mut self) -> &mut f32 {
+        &mut self.channels[3]
+    }
+
+    pub fn new(l: f32, c: f32, h: f32, alpha: f32) -> Oklcha {
+        Oklcha {
+            channels: [l, c, h, alpha],
+        }
+    }
+
+    pub fn from_components(oklch: [f32; 4]) -> Oklcha {
+        Oklcha { channels: oklch }
+    }
+}
+
+impl Pixel for Oklcha {
+    type Subpixel = f32;
+
+    const CHANNEL_COUNT: u8 = 4;
+
+    #[inline(always)]
+    fn channels(&self) -> &[f32] {
+        &self.channels
+    }
+
+    #[inline(always)]
+    fn channels_mut(&mut self) -> &mut [f32] {
+        &mut self.channels
+    }
+
+    const COLOR_MODEL: &'static str = "OKLCHA";
+
+    fn channels4(&self) -> (f32, f32, f32, f32) {
+        (
+            self.channels[0],
+            self.channels[1],
+            self.channels[2],
+            self.channels[3],
+        )
+    }
+
+    fn from_channels(l: f32, c: f32, h: f32, alpha: f32) -> Oklcha {
+        Oklcha::new(l, c, h, alpha)
+    }
+
+    fn from_slice(slice: &[f32]) -> &Oklcha {
+        assert_eq!(slice.len(), 4);
+        unsafe { &*(slice.as_ptr() as *const Oklcha) }
+    }
+
+    fn from_slice_mut(slice: &mut [f32]) -> &mut Oklcha {
+        assert_eq!(slice.len(), 4);
+        unsafe { &mut *(slice.as_mut_ptr() as *mut Oklcha) }
+    }
+
+    fn to_rgb(&self) -> Rgb<f32> {
+        self.to_rgba().to_rgb()
+    }
+
+    fn to_rgba(&self) -> Rgba<f32> {
+        <Self as Into<Rgba<f32>>>::into(*self)
+    }
+
+    fn to_luma(&self) -> Luma<f32> {
+        Luma([*self.l()])
+    }
+
+    fn to_luma_alpha(&self) -> LumaA<f32> {
+        LumaA([*self.l(), *self.alpha()])
+    }
+
+    fn map<F>(&self, f: F) -> Oklcha
+    where
+        F: FnMut(f32) -> f32,
+    {
+        let mut this = *self;
+        this.apply(f);
+        this
+    }
+
+    fn apply<F>(&mut self, mut f: F)
+    where
+        F: FnMut(f32) -> f32,
+    {
+        for v in &mut self.channels {
+            *v = f(*v)
+        }
+    }
+
+    fn map_with_alpha<F, G>(&self, f: F, g: G) -> Oklcha
+    where
+        F: FnMut(f32) -> f32,
+        G: FnMut(f32) -> f32,
+    {
+        let mut this = *self;
+        this.apply_with_alpha(f, g);
+        this
+    }
+
+    fn apply_with_alpha<F, G>(&mut self, mut f: F, mut g: G)
+    where
+        F: FnMut(f32) -> f32,
+        G: FnMut(f32) -> f32,
+    {
+        for v in self.channels[..3].iter_mut() {
+            *v = f(*v)
+        }
+        self.channels[3] = g(self.channels[3]);
+    }
+
+    fn map2<F>(&self, other: &Self, f: F) -> Oklcha
+    where
+        F: FnMut(f32, f32) -> f32,
+    {
+        let mut this = *self;
+        this.apply2(other, f);
+        this
+    }
+
+    fn apply2<F>(&mut self, other: &Oklcha, mut f: F)
+    where
+        F: FnMut(f32, f32) -> f32,
+    {
+        for (a, &b) in self.channels.iter_mut().zip(other.channels.iter()) {
+            *a = f(*a, b)
+        }
+    }
+
+    fn invert(&mut self) {
+        *self.h_mut() = (*self.h() + 180.0) % 360.0;
+    }
+
+    fn blend(&mut self, _other: &Oklcha) {}
+}
+
+impl Deref for Oklcha {
+    type Target = [f32; 4];
+    fn deref(&self) -> &Self::Target {
+        &self.channels
+    }
+}
+
+impl DerefMut for Oklcha {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.channels
+    }
+}
+
+impl From<Oklaba> for Oklcha {
+    fn from(value: Oklaba) -> Self {
+        let [l, c, h] = lab_to_lch(*value.l(), *value.a(), *value.b());
+        Oklcha::new(l, c, h, *value.alpha())
+    }
+}
+
+impl From<Oklcha> for Oklaba {
+    fn from(value: Oklcha) -> Self {
+        let [l, a, b] = lch_to_lab(*value.l(), *value.c(), *value.h());
+        Oklaba::new(l, a, b, *value.alpha())
+    }
+}
+
+impl From<Oklcha> for Rgba<u8> {
+    fn from(value: Oklcha) -> Rgba<u8> {
+        Rgba::from(Oklaba::from(value))
+    }
+}
+
+impl From<Oklcha> for Rgba<u16> {
+    fn from(value: Oklcha) -> Rgba<u16> {
+        Rgba::from(Oklaba::from(value))
+    }
+}
+
+impl From<Oklcha> for Rgba<f32> {
+    fn from(value: Oklcha) -> Rgba<f32> {
+        Rgba::from(Oklaba::from(value))
+    }
+}
+
+impl<T: Primitive + AsFloat> From<Rgba<T>> for Oklcha {
+    fn from(rgb: Rgba<T>) -> Self {
+        Oklcha::from(Oklaba::from(rgb))
+    }
+}
+
+pub type OklchaImage = ImageBuffer<Oklcha, Vec<f32>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oklab_roundtrip() {
+        for lab in [
+            Oklaba::new(0.6, 0.1, -0.05, 1.0),
+            Oklaba::new(0.3, -0.12, 0.02, 0.5),
+            Oklaba::new(0.9, 0.0, 0.0, 1.0),
+        ] {
+            let back = Oklaba::from(Oklcha::from(lab));
+            for (a, b) in lab.channels().iter().zip(back.channels()) {
+                assert!((a - b).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn hue_is_positive_degrees() {
+        let lch = Oklcha::from(Oklaba::new(0.5, 0.0, -0.1, 1.0));
+        assert!((lch.h() - 270.0).abs() < 1e-4);
+        assert!((lch.c() - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rgba_roundtrip() {
+        let rgb = Rgba([200u8, 30, 90, 255]);
+        let back = Rgba::<u8>::from(Oklcha::from(rgb));
+        for (a, b) in rgb.channels().iter().zip(back.channels()) {
+            assert!((*a as i16 - *b as i16).abs() <= 1);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use rayon::prelude::*;
+
+use crate::oklab::OklabaImage;
+
+// distance in the OkLab a/b plane reached by a color range with tolerance 1
+pub const MAX_COLOR_DISTANCE: f32 = 0.2;
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// weight of a lightness for a luminance range going from `low` to `high`,
+/// the weight fades out within `falloff` outside the range
+pub fn luminance_weight(lightness: f32, low: f32, high: f32, falloff: f32) -> f32 {
+    smoothstep(low - falloff, low, lightness) * (1.0 - smoothstep(high, high + falloff, lightness))
+}
+
+/// weights of the luminance range of an OkLab image, stored row by row, from 0 to 1
+pub fn luminance_range(img: &OklabaImage, low: f32, high: f32, falloff: f32) -> Vec<f32> {
+    let pixels: Vec<_> = img.pixels().collect();
+    pixels
+        .par_iter()
+        .map(|p| luminance_weight(*p.l(), low, high, falloff))
+        .collect()
+}
+
+/// mean a/b of the pixels of an OkLab image within `radius` pixels from (x, y),
+/// the lightness is ignored so the same color is selected in light and shadow
+pub fn sample_color(img: &OklabaImage, x: u32, y: u32, radius: u32) -> [f32; 2] {
+    let (width, height) = img.dimensions();
+    if width == 0 || height == 0 {
+        return [0.0; 2];
+    }
+    let (x, y) = (x.min(width - 1), y.min(height - 1));
+
+    let mut sum = [0.0; 2];
+    let mut count = 0.0;
+    for j in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+        for i in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+            let p = img.get_pixel(i, j);
+            sum[0] += p.a();
+            sum[1] += p.b();
+            count += 1.0;
+        }
+    }
+    [sum[0] / count, sum[1] / count]
+}
+
+/// weights of the pixels of an OkLab image close to the `reference` a/b, stored row by row.
+/// The distance in the a/b plane accounts for both the hue and the chroma,
+/// the weight fades out from half of the tolerance to the tolerance, from 0 to 1
+pub fn color_range(img: &OklabaImage, reference: [f32; 2], tolerance: f32) -> Vec<f32> {
+    let limit = tolerance.max(1e-3) * MAX_COLOR_DISTANCE;
+    let pixels: Vec<_> = img.pixels().collect();
+    pixels
+        .par_iter()
+        .map(|p| {
+            let distance = (p.a() - reference[0]).hypot(p.b() - reference[1]);
+            1.0 - smoothstep(0.5 * limit, limit, distance)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // left half a bright blue sky, right half a dark green field
+    fn landscape() -> OklabaImage {
+        let data = (0..16 * 8)
+            .flat_map(|i| {
+                if i % 16 < 8 {
+                    [0.8, -0.05, -0.12, 1.0]
+                } else {
+                    [0.3, -0.1, 0.08, 1.0]
+                }
+            })
+            .collect();
+        OklabaImage::from_raw(16, 8, data).unwrap()
+    }
+
+    #[test]
+    fn luminance_range_has_soft_edges() {
+        assert_eq!(luminance_weight(0.5, 0.4, 0.6, 0.1), 1.0);
+        assert_eq!(luminance_weight(0.2, 0.4, 0.6, 0.1), 0.0);
+        assert_eq!(luminance_weight(0.8, 0.4, 0.6, 0.1), 0.0);
+
+        let w = luminance_weight(0.65, 0.4, 0.6, 0.1);
+        assert!(w > 0.0 && w < 1.0);
+    }
+
+    #[test]
+    fn selects_the_bright_areas() {
+        let weights = luminance_range(&landscape(), 0.6, 1.0, 0.05);
+        assert_eq!(weights[0], 1.0);
+        assert_eq!(weights[15], 0.0);
+    }
+
+    #[test]
+    fn selects_the_sampled_color() {
+        let img = landscape();
+        let sky = sample_color(&img, 2, 4, 2);
+        assert!((sky[0] + 0.05).abs() < 1e-6 && (sky[1] + 0.12).abs() < 1e-6);
+
+        let weights = color_range(&img, sky, 0.3);
+        assert_eq!(weights[16], 1.0);
+        assert_eq!(weights[31], 0.0);
+    }
+}
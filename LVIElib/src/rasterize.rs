@@ -0,0 +1,232 @@
+use rayon::prelude::*;
+
+// sub-scanlines sampled inside every row of pixels
+const SUBSAMPLES: usize = 5;
+
+// distance between two dabs of a stroke relative to the radius of the brush
+const DAB_SPACING: f32 = 0.25;
+
+// adds the coverage of the horizontal span [from, to) to the row
+fn add_span(row: &mut [f32], from: f32, to: f32, weight: f32) {
+    let from = from.clamp(0.0, row.len() as f32);
+    let to = to.clamp(0.0, row.len() as f32);
+    if to <= from {
+        return;
+    }
+
+    let (first, last) = (from as usize, to as usize);
+    if first == last {
+        row[first] += (to - from) * weight;
+        return;
+    }
+
+    row[first] += (first as f32 + 1.0 - from) * weight;
+    for v in &mut row[first + 1..last] {
+        *v += weight;
+    }
+    if last < row.len() {
+        row[last] += (to - last as f32) * weight;
+    }
+}
+
+/// anti-aliased scanline fill of a closed polygon with the even-odd rule,
+/// the points are in pixels and the polygon is closed between the last and the first point.
+/// Returns the coverage of every pixel, from 0 to 1, stored row by row
+pub fn fill_polygon(polygon: &[[f32; 2]], width: usize, height: usize) -> Vec<f32> {
+    let mut out = vec![0f32; width * height];
+    if polygon.len() < 3 || width == 0 {
+        return out;
+    }
+
+    out.par_chunks_mut(width).enumerate().for_each(|(y, row)| {
+        let mut crossings: Vec<f32> = Vec::new();
+
+        for s in 0..SUBSAMPLES {
+            let yc = y as f32 + (s as f32 + 0.5) / SUBSAMPLES as f32;
+
+            crossings.clear();
+            for i in 0..polygon.len() {
+                let (a, b) = (polygon[i], polygon[(i + 1) % polygon.len()]);
+                if (a[1] <= yc) != (b[1] <= yc) {
+                    crossings.push(a[0] + (yc - a[1]) * (b[0] - a[0]) / (b[1] - a[1]));
+                }
+            }
+            crossings.sort_by(|a, b| a.total_cmp(b));
+
+            for pair in crossings.chunks_exact(2) {
+                add_span(row, pair[0], pair[1], 1.0 / SUBSAMPLES as f32);
+            }
+        }
+
+        row.iter_mut().for_each(|v| *v = v.min(1.0));
+    });
+
+    out
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    if edge1 <= edge0 {
+        return if x < edge0 { 0.0 } else { 1.0 };
+    }
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// round brush used by `paint_stroke`, the radius is in pixels. `flow` is the coverage
+/// added by every dab and `hardness` the fraction of the radius painted at full flow,
+/// both from 0 to 1. An erasing brush lowers the weights instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Brush {
+    pub radius: f32,
+    pub flow: f32,
+    pub hardness: f32,
+    pub erase: bool,
+}
+
+// stamps a round dab of the brush centered in `center`
+fn paint_dab(weights: &mut [f32], width: usize, height: usize, center: [f32; 2], brush: &Brush) {
+    let radius = brush.radius;
+    let y0 = (center[1] - radius).floor().max(0.0) as usize;
+    let y1 = ((center[1] + radius).ceil().max(0.0) as usize).min(height);
+    let x0 = (center[0] - radius).floor().max(0.0) as usize;
+    let x1 = ((center[0] + radius).ceil().max(0.0) as usize).min(width);
+    if y0 >= y1 || x0 >= x1 {
+        return;
+    }
+
+    let flow = brush.flow.clamp(0.0, 1.0);
+    let hard_radius = brush.hardness.clamp(0.0, 1.0) * radius;
+
+    weights[y0 * width..y1 * width]
+        .par_chunks_mut(width)
+        .enumerate()
+        .for_each(|(j, row)| {
+            let dy = (y0 + j) as f32 + 0.5 - center[1];
+            for (i, w) in row[x0..x1].iter_mut().enumerate() {
+                let dx = (x0 + i) as f32 + 0.5 - center[0];
+                let a = flow * (1.0 - smoothstep(hard_radius, radius, dx.hypot(dy)));
+                if brush.erase {
+                    *w *= 1.0 - a;
+                } else {
+                    *w += a * (1.0 - *w);
+                }
+            }
+        });
+}
+
+/// paints a stroke of round dabs on a weight map stored row by row,
+/// the points of the stroke are in pixels
+pub fn paint_stroke(
+    weights: &mut [f32],
+    width: usize,
+    height: usize,
+    points: &[[f32; 2]],
+    brush: &Brush,
+) {
+    assert_eq!(weights.len(), width * height);
+    let (Some(first), true) = (points.first(), brush.radius > 0.0) else {
+        return;
+    };
+    let spacing = (DAB_SPACING * brush.radius).max(0.5);
+
+    paint_dab(weights, width, height, *first, brush);
+
+    // the dabs are evenly spaced along the stroke, the remainder moves to the next segment
+    let mut travelled = 0.0;
+    for segment in points.windows(2) {
+        let (a, b) = (segment[0], segment[1]);
+        let length = (b[0] - a[0]).hypot(b[1] - a[1]);
+
+        let mut t = spacing - travelled;
+        while t <= length {
+            let center = [
+                a[0] + (b[0] - a[0]) * t / length,
+                a[1] + (b[1] - a[1]) * t / length,
+            ];
+            paint_dab(weights, width, height, center, brush);
+            t += spacing;
+        }
+        travelled = length - (t - spacing);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_the_whole_image() {
+        let square = [[0.0, 0.0], [8.0, 0.0], [8.0, 8.0], [0.0, 8.0]];
+        let out = fill_polygon(&square, 8, 8);
+        assert!(out.iter().all(|v| (v - 1.0).abs() < 1e-5));
+    }
+
+    #[test]
+    fn edges_are_anti_aliased() {
+        // the right edge crosses the middle of the third column
+        let rect = [[0.0, 0.0], [2.5, 0.0], [2.5, 4.0], [0.0, 4.0]];
+        let out = fill_polygon(&rect, 4, 4);
+        assert!((out[1] - 1.0).abs() < 1e-5);
+        assert!((out[2] - 0.5).abs() < 1e-5);
+        assert_eq!(out[3], 0.0);
+    }
+
+    #[test]
+    fn area_is_preserved() {
+        let triangle = [[2.0, 2.0], [30.0, 5.0], [10.0, 28.0]];
+        let area = 0.5 * ((30.0 - 2.0) * (28.0 - 2.0) - (10.0 - 2.0) * (5.0 - 2.0));
+        let out = fill_polygon(&triangle, 32, 32);
+        let sum: f32 = out.iter().sum();
+        assert!((sum - area).abs() / area < 0.01);
+    }
+
+    #[test]
+    fn hard_dab_covers_a_disc() {
+        let mut w = vec![0f32; 32 * 32];
+        let brush = Brush {
+            radius: 8.0,
+            flow: 1.0,
+            hardness: 1.0,
+            erase: false,
+        };
+        paint_stroke(&mut w, 32, 32, &[[16.0, 16.0]], &brush);
+
+        let area = std::f32::consts::PI * 64.0;
+        let sum: f32 = w.iter().sum();
+        assert!((sum - area).abs() / area < 0.05);
+        assert_eq!(w[16 * 32 + 16], 1.0);
+        assert_eq!(w[0], 0.0);
+    }
+
+    #[test]
+    fn flow_builds_up_along_the_stroke() {
+        let mut w = vec![0f32; 64 * 16];
+        let line = [[8.0, 8.0], [56.0, 8.0]];
+        let brush = Brush {
+            radius: 4.0,
+            flow: 0.2,
+            hardness: 0.5,
+            erase: false,
+        };
+        paint_stroke(&mut w, 64, 16, &line, &brush);
+
+        // a single dab at the start, many overlapping dabs in the middle
+        let single = 0.2;
+        assert!(w[8 * 64 + 32] > single && w[8 * 64 + 32] <= 1.0);
+        assert_eq!(w[8 * 64 + 63], 0.0);
+    }
+
+    #[test]
+    fn erasing_removes_the_weights() {
+        let mut w = vec![1f32; 32 * 32];
+        let brush = Brush {
+            radius: 6.0,
+            flow: 1.0,
+            hardness: 1.0,
+            erase: true,
+        };
+        paint_stroke(&mut w, 32, 32, &[[0.0, 16.0], [32.0, 16.0]], &brush);
+        assert_eq!(w[16 * 32 + 16], 0.0);
+        assert_eq!(w[0], 1.0);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::hsl::{Hsl, Hsla, HslaImage};
 use crate::oklab::{Oklab, Oklaba};
+use crate::oklch::{Oklch, Oklcha};
 use image::{Pixel, Rgb, Rgba};
 
 pub trait ToHsl {
@@ -77,6 +78,24 @@ impl ToHsl for Oklaba {
     }
 }
 
+impl ToHsl for Oklch {
+    fn to_hsl(&self) -> Hsl {
+        self.to_rgb().into()
+    }
+    fn to_hsla(&self) -> Hsla {
+        self.to_rgba().into()
+    }
+}
+
+impl ToHsl for Oklcha {
+    fn to_hsl(&self) -> Hsl {
+        self.to_rgb().into()
+    }
+    fn to_hsla(&self) -> Hsla {
+        self.to_rgba().into()
+    }
+}
+
 use image::GenericImageView;
 use num_traits::ToPrimitive;
 
@@ -178,3 +197,114 @@ impl ToOklab for Oklaba {
         self.clone()
     }
 }
+
+impl ToOklab for Oklch {
+    fn to_oklab(&self) -> Oklab {
+        Oklab::from(*self)
+    }
+    fn to_oklaba(&self) -> Oklaba {
+        self.to_oklab().to_oklaba()
+    }
+}
+
+impl ToOklab for Oklcha {
+    fn to_oklab(&self) -> Oklab {
+        Oklaba::from(*self).to_oklab()
+    }
+    fn to_oklaba(&self) -> Oklaba {
+        Oklaba::from(*self)
+    }
+}
+
+pub trait ToOklch {
+    fn to_oklch(&self) -> Oklch;
+    fn to_oklcha(&self) -> Oklcha;
+}
+
+macro_rules! impl_ToOklch_for_Rgb {
+    ($t: ty) => {
+        impl ToOklch for Rgb<$t> {
+            fn to_oklch(&self) -> Oklch {
+                Oklch::from(*self)
+            }
+            fn to_oklcha(&self) -> Oklcha {
+                Oklcha::from(self.to_rgba())
+            }
+        }
+    };
+}
+
+impl_ToOklch_for_Rgb!(u8);
+impl_ToOklch_for_Rgb!(u16);
+impl_ToOklch_for_Rgb!(f32);
+
+macro_rules! impl_ToOklch_for_Rgba {
+    ($t: ty) => {
+        impl ToOklch for Rgba<$t> {
+            fn to_oklch(&self) -> Oklch {
+                Oklch::from(self.to_rgb())
+            }
+            fn to_oklcha(&self) -> Oklcha {
+                Oklcha::from(*self)
+            }
+        }
+    };
+}
+
+impl_ToOklch_for_Rgba!(u8);
+impl_ToOklch_for_Rgba!(u16);
+impl_ToOklch_for_Rgba!(f32);
+
+impl ToOklch for Hsl {
+    fn to_oklch(&self) -> Oklch {
+        self.to_oklab().into()
+    }
+    fn to_oklcha(&self) -> Oklcha {
+        self.to_oklaba().into()
+    }
+}
+
+impl ToOklch for Hsla {
+    fn to_oklch(&self) -> Oklch {
+        self.to_oklab().into()
+    }
+    fn to_oklcha(&self) -> Oklcha {
+        self.to_oklaba().into()
+    }
+}
+
+impl ToOklch for Oklab {
+    fn to_oklch(&self) -> Oklch {
+        Oklch::from(*self)
+    }
+    fn to_oklcha(&self) -> Oklcha {
+        Oklcha::from(self.to_oklaba())
+    }
+}
+
+impl ToOklch for Oklaba {
+    fn to_oklch(&self) -> Oklch {
+        Oklch::from(self.to_oklab())
+    }
+    fn to_oklcha(&self) -> Oklcha {
+        Oklcha::from(*self)
+    }
+}
+
+impl ToOklch for Oklch {
+    fn to_oklch(&self) -> Oklch {
+        *self
+    }
+    fn to_oklcha(&self) -> Oklcha {
+        Oklcha::new(*self.l(), *self.c(), *self.h(), 1.0)
+    }
+}
+
+impl ToOklch for Oklcha {
+    fn to_oklch(&self) -> Oklch {
+        Oklch::new(*self.l(), *self.c(), *self.h())
+    }
+    fn to_oklcha(&self) -> Oklcha {
+        *self
+    }
+}
@@ -0,0 +1,152 @@
+use rayon::prelude::*;
+
+pub const WAVELET_SCALES: usize = 5;
+
+// B3 spline used by the à trous algorithm
+const KERNEL: [f32; 5] = [1.0 / 16.0, 4.0 / 16.0, 6.0 / 16.0, 4.0 / 16.0, 1.0 / 16.0];
+
+// smooths a single channel image with the B3 spline spread by `step` pixels, the edges are extended
+fn smooth(data: &[f32], width: usize, height: usize, step: usize) -> Vec<f32> {
+    let clamp = |i: isize, max: usize| i.clamp(0, max as isize - 1) as usize;
+
+    let mut rows = vec![0f32; data.len()];
+    rows.par_chunks_mut(width)
+        .zip(data.par_chunks(width))
+        .for_each(|(out, row)| {
+            for (x, v) in out.iter_mut().enumerate() {
+                *v = KERNEL
+                    .iter()
+                    .enumerate()
+                    .map(|(k, w)| {
+                        w * row[clamp(x as isize + (k as isize - 2) * step as isize, width)]
+                    })
+                    .sum();
+            }
+        });
+
+    let mut out = vec![0f32; data.len()];
+    out.par_chunks_mut(width).enumerate().for_each(|(y, out)| {
+        for (x, v) in out.iter_mut().enumerate() {
+            *v = KERNEL
+                .iter()
+                .enumerate()
+                .map(|(k, w)| {
+                    w * rows
+                        [clamp(y as isize + (k as isize - 2) * step as isize, height) * width + x]
+                })
+                .sum();
+        }
+    });
+    out
+}
+
+/// à trous wavelet decomposition of a single channel image stored row by row,
+/// returns the detail layers from the finest to the coarsest followed by the residual.
+/// The sum of all the layers gives back the original image
+pub fn atrous_decompose(data: &[f32], width: usize, height: usize, scales: usize) -> Vec<Vec<f32>> {
+    let mut layers = Vec::with_capacity(scales + 1);
+    let mut current = data.to_vec();
+
+    for scale in 0..scales {
+        let smoothed = smooth(&current, width, height, 1 << scale);
+        layers.push(current.iter().zip(&smoothed).map(|(c, s)| c - s).collect());
+        current = smoothed;
+    }
+
+    layers.push(current);
+    layers
+}
+
+// robust estimation of the noise of a detail layer (median absolute deviation)
+fn noise_sigma(detail: &[f32]) -> f32 {
+    let mut abs: Vec<f32> = detail.iter().map(|v| v.abs()).collect();
+    let mid = abs.len() / 2;
+    let (_, median, _) = abs.select_nth_unstable_by(mid, |a, b| a.total_cmp(b));
+    *median / 0.6745
+}
+
+/// removes the noise of a single channel image stored row by row.
+/// `strength` goes from 0 to 1, the coefficients of every scale below `threshold` times
+/// the estimated noise are shrunk towards 0 and `scale_strengths` weights every scale,
+/// from the finest to the coarsest
+pub fn denoise_channel(
+    data: &mut [f32],
+    width: usize,
+    height: usize,
+    strength: f32,
+    threshold: f32,
+    scale_strengths: &[f32],
+) {
+    assert_eq!(data.len(), width * height);
+    if strength <= 0.0 || scale_strengths.iter().all(|s| *s <= 0.0) {
+        return;
+    }
+
+    let mut layers = atrous_decompose(data, width, height, scale_strengths.len());
+    let residual = layers.pop().unwrap();
+    data.copy_from_slice(&residual);
+
+    for (detail, scale_strength) in layers.iter_mut().zip(scale_strengths) {
+        let t = threshold * noise_sigma(detail);
+        let amount = (strength * scale_strength).clamp(0.0, 1.0);
+
+        detail.par_iter_mut().for_each(|c| {
+            // soft thresholding, the coefficients above the threshold are details
+            let shrunk = c.signum() * (c.abs() - t).max(0.0);
+            *c -= amount * (*c - shrunk);
+        });
+
+        data.iter_mut()
+            .zip(detail.iter())
+            .for_each(|(v, d)| *v += d);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // deterministic noise in -0.5..0.5
+    fn noise(len: usize) -> Vec<f32> {
+        let mut state = 12345u32;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1664525).wrapping_add(1013904223);
+                (state >> 8) as f32 / (1 << 24) as f32 - 0.5
+            })
+            .collect()
+    }
+
+    fn variance(data: &[f32]) -> f32 {
+        let mean = data.iter().sum::<f32>() / data.len() as f32;
+        data.iter().map(|v| (v - mean).powi(2)).sum::<f32>() / data.len() as f32
+    }
+
+    #[test]
+    fn decomposition_is_exact() {
+        let data = noise(40 * 30);
+        let layers = atrous_decompose(&data, 40, 30, WAVELET_SCALES);
+        assert_eq!(layers.len(), WAVELET_SCALES + 1);
+
+        for (i, v) in data.iter().enumerate() {
+            let sum: f32 = layers.iter().map(|l| l[i]).sum();
+            assert!((sum - v).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn zero_strength_does_nothing() {
+        let original = noise(32 * 32);
+        let mut data = original.clone();
+        denoise_channel(&mut data, 32, 32, 0.0, 1.0, &[1.0; WAVELET_SCALES]);
+        assert_eq!(data, original);
+    }
+
+    #[test]
+    fn removes_noise() {
+        let mut data: Vec<f32> = noise(64 * 64).iter().map(|n| 0.5 + 0.05 * n).collect();
+        let before = variance(&data);
+        denoise_channel(&mut data, 64, 64, 1.0, 3.0, &[1.0; WAVELET_SCALES]);
+        assert!(variance(&data) < before * 0.25);
+    }
+}
@@ -29,6 +29,29 @@ pub const XYZ_TO_LINSRGB: [f32; 9] = [
     1.0572252,
 ];
 
+// Bradford cone response, source: Wikipedia
+const XYZ_TO_LMS: [f32; 9] = [
+    0.8951, 0.2664, -0.1614, -0.7502, 1.7135, 0.0367, 0.0389, -0.0685, 1.0296,
+];
+
+// source: WolframAlpha
+const LMS_TO_XYZ: [f32; 9] = [
+    0.986993,
+    -0.147054,
+    0.159963,
+    0.432305,
+    0.51836,
+    0.0492912,
+    -0.00852866,
+    0.0400428,
+    0.968487,
+];
+
+// product of a row major 3x3 matrix and a vector
+fn transform(m: &[f32; 9], v: [f32; 3]) -> [f32; 3] {
+    std::array::from_fn(|r| m[3 * r] * v[0] + m[3 * r + 1] * v[1] + m[3 * r + 2] * v[2])
+}
+
 pub fn uv_white_point(temp: f32, tint: f32) -> (f32, f32) {
     // Planck's locus in uv chromacity coordinates
     let u = (WP_U[0] + WP_U[1] * temp + WP_U[2] * temp * temp)
@@ -50,6 +73,64 @@ pub fn uv_white_point(temp: f32, tint: f32) -> (f32, f32) {
     (u + tint * dv / 1000.0, v - tint * du / 1000.0)
 }
 
+// the tint moves the white point by 1 / 1000 of the normal to Planck's locus
+const TINT_SCALE: f32 = 1000.0;
+
+// range of the temperatures searched by `temp_tint_from_uv`
+const MIN_TEMP: f32 = 1000.0;
+const MAX_TEMP: f32 = 25000.0;
+
+/// inverse of `uv_white_point`: the temperature of the nearest point of Planck's locus and the
+/// tint that moves it to (u, v) along the isothermal line
+pub fn temp_tint_from_uv(u: f32, v: f32) -> (f32, f32) {
+    // component of the distance from the locus along its tangent, it is zero at the solution
+    let along = |temp: f32| {
+        let (lu, lv) = uv_white_point(temp, 0.0);
+        let (nu, nv) = uv_white_point(temp, TINT_SCALE);
+        let (nu, nv) = (nu - lu, nv - lv);
+        (
+            -nv * (u - lu) + nu * (v - lv),
+            (nu * (u - lu) + nv * (v - lv)) * TINT_SCALE,
+        )
+    };
+
+    let steps = ((MAX_TEMP - MIN_TEMP) / 50.0) as usize;
+    let temps: Vec<f32> = (0..=steps).map(|i| MIN_TEMP + i as f32 * 50.0).collect();
+    let bracket = temps
+        .windows(2)
+        .find(|w| along(w[0]).0.signum() != along(w[1]).0.signum());
+
+    let temp = match bracket {
+        Some(w) => {
+            let (mut low, mut high) = (w[0], w[1]);
+            let low_sign = along(low).0.signum();
+            for _ in 0..30 {
+                let mid = (low + high) / 2.0;
+                if along(mid).0.signum() == low_sign {
+                    low = mid;
+                } else {
+                    high = mid;
+                }
+            }
+            (low + high) / 2.0
+        }
+        // the point is beyond the ends of the searched range
+        None => *temps
+            .iter()
+            .min_by(|a, b| along(**a).0.abs().total_cmp(&along(**b).0.abs()))
+            .unwrap(),
+    };
+
+    (temp, along(temp).1)
+}
+
+pub fn xy_to_uv(x: f32, y: f32) -> (f32, f32) {
+    (
+        4.0 * x / (-2.0 * x + 12.0 * y + 3.0),
+        6.0 * y / (-2.0 * x + 12.0 * y + 3.0),
+    )
+}
+
 pub fn uv_to_xy(u: f32, v: f32) -> (f32, f32) {
     (
         3.0 * u / (2.0 * u - 8.0 * v + 4.0),
@@ -77,29 +158,8 @@ pub fn xy_white_point(temp: f32) -> (f32, f32) {
 }
 
 pub fn xyz_wb_matrix(fromtemp: f32, fromtint: f32, totemp: f32, totint: f32) -> Matrix<f32> {
-    // source: Wikipedia
-    let xyz_to_lms = Matrix::from_rows(vec![
-        vec![0.8951, 0.2664, -0.1614],
-        vec![-0.7502, 1.7135, 0.0367],
-        vec![0.0389, -0.0685, 1.0296],
-    ]);
-
-    // source: WolframAlpha
-    let lms_to_xyz = Matrix::new(
-        vec![
-            0.986993,
-            -0.147054,
-            0.159963,
-            0.432305,
-            0.51836,
-            0.0492912,
-            -0.00852866,
-            0.0400428,
-            0.968487,
-        ],
-        3,
-        3,
-    );
+    let xyz_to_lms = Matrix::new(XYZ_TO_LMS.to_vec(), 3, 3);
+    let lms_to_xyz = Matrix::new(LMS_TO_XYZ.to_vec(), 3, 3);
 
     let (u, v) = uv_white_point(fromtemp, fromtint);
     let (x, y) = uv_to_xy(u, v);
@@ -129,6 +189,78 @@ pub fn xyz_wb_matrix(fromtemp: f32, fromtint: f32, totemp: f32, totint: f32) ->
     ((lms_to_xyz * diag).unwrap() * xyz_to_lms).unwrap()
 }
 
+// cone response of the white point (temp, tint)
+fn white_point_lms(temp: f32, tint: f32) -> [f32; 3] {
+    let (u, v) = uv_white_point(temp, tint);
+    let (x, y) = uv_to_xy(u, v);
+    transform(&XYZ_TO_LMS, [x / y, 1.0, (1.0 - x - y) / y])
+}
+
+/// the temperature and the tint that make the linear sRGB color `rgb` neutral,
+/// `rgb` comes from an image rendered with the white point (`temp`, `tint`)
+pub fn neutralizing_white_point(rgb: [f32; 3], temp: f32, tint: f32) -> (f32, f32) {
+    // the white balance scales the cone responses by the ratio between two white points,
+    // so the new white point is the current one scaled by the cast of `rgb`
+    let color = transform(&XYZ_TO_LMS, transform(&LINSRGB_TO_XYZ, rgb));
+    let gray = transform(&XYZ_TO_LMS, transform(&LINSRGB_TO_XYZ, [1.0; 3]));
+    let current = white_point_lms(temp, tint);
+    let lms: [f32; 3] = std::array::from_fn(|c| current[c] * color[c] / gray[c]);
+
+    let [x, y, z] = transform(&LMS_TO_XYZ, lms);
+    let (u, v) = xy_to_uv(x / (x + y + z), y / (x + y + z));
+    temp_tint_from_uv(u, v)
+}
+
+/// ways to estimate the color of the light of a scene from its pixels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AutoWhiteBalance {
+    /// the average of the scene is gray
+    GrayWorld,
+    /// the brightest value of every channel, ignoring the clipped pixels, is white
+    WhitePatch,
+    /// like `WhitePatch` with the given percentile (0 to 1) of every channel,
+    /// so that a few very bright pixels do not decide the white point
+    Percentile(f32),
+}
+
+/// the color of the light of an image in linear sRGB, it is neutral in a balanced image
+pub fn estimate_illuminant(pixels: &[[f32; 3]], method: AutoWhiteBalance) -> [f32; 3] {
+    if pixels.is_empty() {
+        return [1.0; 3];
+    }
+
+    match method {
+        AutoWhiteBalance::GrayWorld => {
+            let mut sum = [0f64; 3];
+            for p in pixels {
+                for (s, v) in sum.iter_mut().zip(p) {
+                    *s += *v as f64;
+                }
+            }
+            sum.map(|s| (s / pixels.len() as f64) as f32)
+        }
+        AutoWhiteBalance::WhitePatch => {
+            let mut max = [0f32; 3];
+            for p in pixels.iter().filter(|p| p.iter().all(|v| *v < 0.999)) {
+                for (m, v) in max.iter_mut().zip(p) {
+                    *m = m.max(*v);
+                }
+            }
+            // an image without unclipped colors gives no information
+            if max.contains(&0.0) {
+                [1.0; 3]
+            } else {
+                max
+            }
+        }
+        AutoWhiteBalance::Percentile(percentile) => std::array::from_fn(|c| {
+            let mut channel: Vec<f32> = pixels.iter().map(|p| p[c]).collect();
+            let i = ((channel.len() - 1) as f32 * percentile.clamp(0.0, 1.0)) as usize;
+            *channel.select_nth_unstable_by(i, f32::total_cmp).1
+        }),
+    }
+}
+
 /* pub fn wb_matrix(fromtemp: f32, fromtint: f32, totemp: f32, totint: f32) -> Matrix<f32> {
     // source: www.image-engineering.de
     let linrgb_to_xyz = Matrix::new(
@@ -206,8 +338,92 @@ pub fn xyz_wb_matrix(fromtemp: f32, fromtint: f32, totemp: f32, totint: f32) ->
 
 #[cfg(test)]
 mod test {
+    use super::*;
     use crate::matrix::Matrix;
 
+    fn assert_white_point((temp, tint): (f32, f32), expected: (f32, f32)) {
+        assert!((temp - expected.0).abs() < 20.0, "{temp} != {}", expected.0);
+        assert!((tint - expected.1).abs() < 0.5, "{tint} != {}", expected.1);
+    }
+
+    // a neutral color as it looks under the white point (temp, tint)
+    fn cast(temp: f32, tint: f32, rgb: [f32; 3]) -> [f32; 3] {
+        let m = Matrix::new(XYZ_TO_LINSRGB.to_vec(), 3, 3);
+        let xyz = (xyz_wb_matrix(temp, tint, 6000.0, 0.0)
+            * Matrix::new(LINSRGB_TO_XYZ.to_vec(), 3, 3))
+        .unwrap();
+        let m = (m * xyz).unwrap().consume_content();
+        transform(&m.try_into().unwrap(), rgb)
+    }
+
+    #[test]
+    fn uv_white_point_is_inverted() {
+        for (temp, tint) in [
+            (3000.0, 0.0),
+            (4500.0, 20.0),
+            (6500.0, -30.0),
+            (9000.0, 10.0),
+        ] {
+            let (u, v) = uv_white_point(temp, tint);
+            assert_white_point(temp_tint_from_uv(u, v), (temp, tint));
+        }
+    }
+
+    #[test]
+    fn gray_is_already_neutral() {
+        assert_white_point(
+            neutralizing_white_point([0.4; 3], 6000.0, 0.0),
+            (6000.0, 0.0),
+        );
+        assert_white_point(
+            neutralizing_white_point([0.4; 3], 5000.0, 10.0),
+            (5000.0, 10.0),
+        );
+    }
+
+    #[test]
+    fn neutralizes_a_color_cast() {
+        let picked = cast(4500.0, 20.0, [0.5; 3]);
+        assert!(picked[0] > picked[2]);
+        assert_white_point(
+            neutralizing_white_point(picked, 6000.0, 0.0),
+            (4500.0, 20.0),
+        );
+    }
+
+    #[test]
+    fn estimates_the_illuminant() {
+        let light = cast(4000.0, 0.0, [1.0; 3]);
+        let mut pixels: Vec<[f32; 3]> = (0..100)
+            .map(|i| {
+                // a scene that is gray on average under a warm light
+                let c = [0.2 + 0.006 * i as f32, 0.8 - 0.006 * i as f32, 0.5];
+                std::array::from_fn(|k| c[k] * light[k] * 0.9)
+            })
+            .collect();
+        pixels.push([0.1, 0.1, 0.1]);
+
+        for method in [
+            AutoWhiteBalance::GrayWorld,
+            AutoWhiteBalance::WhitePatch,
+            AutoWhiteBalance::Percentile(0.99),
+        ] {
+            let estimate = estimate_illuminant(&pixels, method);
+            let (temp, _) = neutralizing_white_point(estimate, 6000.0, 0.0);
+            assert!(temp < 5000.0, "{method:?}: {temp}");
+        }
+    }
+
+    #[test]
+    fn percentile_ignores_a_few_bright_pixels() {
+        let mut pixels = vec![[0.5, 0.5, 0.5]; 200];
+        pixels.push([0.2, 0.2, 0.9]);
+        let white_patch = estimate_illuminant(&pixels, AutoWhiteBalance::WhitePatch);
+        let percentile = estimate_illuminant(&pixels, AutoWhiteBalance::Percentile(0.99));
+        assert_eq!(white_patch, [0.5, 0.5, 0.9]);
+        assert_eq!(percentile, [0.5, 0.5, 0.5]);
+    }
+
     #[test]
     fn inverses() {
         let I = Matrix::from_diagonal(vec![1.0, 1.0, 1.0], 0.0);